@@ -0,0 +1,35 @@
+// Colours for everything the game draws. These used to come straight from `tcod::colors`, so the names and values
+// match libtcod's (and so does the save/data file format) - the libtcod front end just converts them as it draws.
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+}
+
+pub const BLACK: Color = Color::new(0, 0, 0);
+pub const WHITE: Color = Color::new(255, 255, 255);
+pub const LIGHT_GREY: Color = Color::new(159, 159, 159);
+
+pub const RED: Color = Color::new(255, 0, 0);
+pub const LIGHT_RED: Color = Color::new(255, 115, 115);
+pub const DARK_RED: Color = Color::new(191, 0, 0);
+pub const DARKER_RED: Color = Color::new(128, 0, 0);
+pub const ORANGE: Color = Color::new(255, 127, 0);
+pub const YELLOW: Color = Color::new(255, 255, 0);
+pub const LIGHT_YELLOW: Color = Color::new(255, 255, 115);
+pub const GREEN: Color = Color::new(0, 255, 0);
+pub const LIGHT_GREEN: Color = Color::new(115, 255, 115);
+pub const LIGHT_CYAN: Color = Color::new(115, 255, 255);
+pub const LIGHT_BLUE: Color = Color::new(115, 115, 255);
+pub const VIOLET: Color = Color::new(127, 0, 255);
+pub const LIGHT_VIOLET: Color = Color::new(185, 115, 255);
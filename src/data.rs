@@ -0,0 +1,447 @@
+// Game content that lives in data files rather than code, so monsters and items can be added or rebalanced without
+// touching Rust. Everything is loaded and checked once at startup - a bad file stops the game with a list of what's
+// wrong with it.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use crate::colors::Color;
+use crate::game::{Effect, Inflicts, Item, Slot, AI, MAP_HEIGHT, MAP_WIDTH, NORMAL_SPEED};
+use crate::mapgen::Generator;
+
+pub const MONSTERS_FILE: &str = "data/monsters.json";
+pub const ITEMS_FILE: &str = "data/items.json";
+pub const TRAPS_FILE: &str = "data/traps.json";
+pub const VAULTS_FILE: &str = "data/vaults.json";
+pub const DUNGEON_FILE: &str = "data/dungeon.json";
+
+// A number that changes as the player goes deeper. Each step holds from its depth until the next one takes over, and
+// it's 0 above the first step. A plain number in the file means the same value at every depth.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "DepthTableFile")]
+pub struct DepthTable {
+    steps: Vec<DepthStep>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepthStep {
+    pub from: i32,
+    pub value: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DepthTableFile {
+    Constant(u32),
+    Steps(Vec<DepthStep>),
+}
+
+impl From<DepthTableFile> for DepthTable {
+    fn from(table: DepthTableFile) -> Self {
+        match table {
+            DepthTableFile::Constant(value) => DepthTable { steps: vec![DepthStep { from: 1, value }] },
+            DepthTableFile::Steps(steps) => DepthTable { steps },
+        }
+    }
+}
+
+impl DepthTable {
+    pub fn at(&self, depth: i32) -> u32 {
+        self.steps.iter().rev().find(|step| depth >= step.from).map_or(0, |step| step.value)
+    }
+
+    fn problems(&self) -> Option<&'static str> {
+        if self.steps.is_empty() {
+            Some("has no steps")
+        } else if self.steps[0].from < 1 {
+            Some("starts above depth 1")
+        } else if self.steps.windows(2).any(|pair| pair[1].from <= pair[0].from) {
+            Some("has steps that aren't in order of depth")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub colour: Color,
+    pub hp: i32,
+    pub defence: i32,
+    pub power: i32,
+    // what the player gets for killing one
+    pub xp: i32,
+    pub ai: AI,
+    // how quickly it acts - 10 is the same as the player, 20 twice as often, 5 half as often
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    // a status effect its attacks can pass on, e.g. poison
+    pub on_hit: Option<Inflicts>,
+    // how likely this monster is to be picked compared to the others that can appear at the same depth
+    pub spawn_weight: DepthTable,
+    pub min_depth: i32,
+    // no maximum means it keeps turning up however deep you go
+    pub max_depth: Option<i32>,
+}
+
+fn normal_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+impl MonsterTemplate {
+    pub fn spawns_at(&self, depth: i32) -> bool {
+        depth >= self.min_depth && self.max_depth.is_none_or(|max_depth| depth <= max_depth)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub colour: Color,
+    // what it does and how strongly, e.g. `{ "kind": "Heal", "amount": 4 }`
+    pub effect: Option<Item>,
+    // or what it gives when worn
+    pub equipment: Option<EquipmentTemplate>,
+    pub spawn_weight: DepthTable,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EquipmentTemplate {
+    pub slot: Slot,
+    #[serde(default)]
+    pub power: i32,
+    #[serde(default)]
+    pub defence: i32,
+    #[serde(default)]
+    pub max_hp: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrapTemplate {
+    pub name: String,
+    // what it looks like once it's been found
+    pub glyph: char,
+    pub colour: Color,
+    pub inflicts: Inflicts,
+    pub spawn_weight: DepthTable,
+}
+
+// A hand drawn room. In `rows` `#` is wall, `.` is floor and a space leaves whatever was there. Any other character
+// has to be in the legend, and is floor with something on it. The way in is wherever there's floor on the edge.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultTemplate {
+    pub name: String,
+    pub rows: Vec<String>,
+    #[serde(default)]
+    pub legend: HashMap<char, VaultCell>,
+    pub spawn_weight: DepthTable,
+}
+
+// What goes on a legend character's tile, by the name of its template
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct VaultCell {
+    pub monster: Option<String>,
+    pub item: Option<String>,
+    pub trap: Option<String>,
+}
+
+impl VaultTemplate {
+    pub fn width(&self) -> i32 {
+        self.rows.first().map_or(0, |row| row.chars().count()) as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.rows.len() as i32
+    }
+
+    // every character with where it is in the template
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32, char)> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, row)|
+            row.chars().enumerate().map(move |(x, chr)| (x as i32, y as i32, chr))
+        )
+    }
+
+    pub fn is_floor(&self, chr: char) -> bool {
+        chr == '.' || self.legend.contains_key(&chr)
+    }
+
+    pub fn on_edge(&self, x: i32, y: i32) -> bool {
+        x == 0 || y == 0 || x == self.width() - 1 || y == self.height() - 1
+    }
+}
+
+// One of the ways a level can be laid out, and how likely it is at each depth
+#[derive(Debug, Clone, Deserialize)]
+pub struct GeneratorChoice {
+    pub generator: Generator,
+    pub weight: DepthTable,
+}
+
+// How the dungeon gets harder the deeper you go
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DungeonSettings {
+    pub generators: Vec<GeneratorChoice>,
+    pub max_room_monsters: DepthTable,
+    pub max_room_items: DepthTable,
+    pub max_room_traps: DepthTable,
+    // how many vaults a level can get, if there's room for them
+    pub max_vaults: DepthTable,
+    // the bottom of the dungeon. There are no stairs down there, just the boss standing guard over the artefact that
+    // the player has to bring back up and out of the dungeon
+    pub final_depth: i32,
+    pub boss: String,
+    pub artefact: String,
+}
+
+#[derive(Debug, Default)]
+pub struct GameData {
+    pub monsters: Vec<MonsterTemplate>,
+    pub items: Vec<ItemTemplate>,
+    pub traps: Vec<TrapTemplate>,
+    pub vaults: Vec<VaultTemplate>,
+    pub dungeon: DungeonSettings,
+}
+
+pub fn load_data() -> Result<GameData, Box<dyn Error>> {
+    let monsters: Vec<MonsterTemplate> = read_json(MONSTERS_FILE)?;
+    check(MONSTERS_FILE, validate_monsters(&monsters))?;
+    let items: Vec<ItemTemplate> = read_json(ITEMS_FILE)?;
+    check(ITEMS_FILE, validate_items(&items))?;
+    let traps: Vec<TrapTemplate> = read_json(TRAPS_FILE)?;
+    check(TRAPS_FILE, validate_traps(&traps))?;
+    let vaults: Vec<VaultTemplate> = read_json(VAULTS_FILE)?;
+    check(VAULTS_FILE, validate_vaults(&vaults, &monsters, &items, &traps))?;
+    let dungeon: DungeonSettings = read_json(DUNGEON_FILE)?;
+    check(DUNGEON_FILE, validate_dungeon(&dungeon, &monsters, &items))?;
+    Ok(GameData { monsters, items, traps, vaults, dungeon })
+}
+
+fn read_json<T: DeserializeOwned>(path: &str) -> Result<T, Box<dyn Error>> {
+    let mut contents = String::new();
+    let mut file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+    file.read_to_string(&mut contents)?;
+    let data = serde_json::from_str(&contents).map_err(|e| format!("{} isn't valid: {}", path, e))?;
+    Ok(data)
+}
+
+// Turn a list of problems into one error that names the file and lists them all
+fn check(path: &str, problems: Vec<String>) -> Result<(), Box<dyn Error>> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} has problems:\n  {}", path, problems.join("\n  ")).into())
+    }
+}
+
+fn validate_monsters(monsters: &[MonsterTemplate]) -> Vec<String> {
+    let mut problems = vec![];
+    if monsters.is_empty() {
+        problems.push("there are no monsters".to_string());
+    }
+
+    for (idx, monster) in monsters.iter().enumerate() {
+        let name = if monster.name.is_empty() { format!("monster {}", idx + 1) } else { monster.name.clone() };
+        if monster.name.is_empty() {
+            problems.push(format!("{} has no name", name));
+        }
+        if monsters[..idx].iter().any(|other| other.name == monster.name) {
+            problems.push(format!("{} is defined more than once", name));
+        }
+        if monster.hp <= 0 {
+            problems.push(format!("{} needs more than 0 hp", name));
+        }
+        if monster.defence < 0 || monster.power < 0 || monster.xp < 0 {
+            problems.push(format!("{} can't have negative defence, power or xp", name));
+        }
+        if monster.speed <= 0 {
+            problems.push(format!("{} needs a speed above 0", name));
+        }
+        if let Some(problem) = monster.on_hit.and_then(|on_hit| inflicts_problems(&on_hit)) {
+            problems.push(format!("{}'s on_hit {}", name, problem));
+        }
+        if let Some(problem) = monster.spawn_weight.problems() {
+            problems.push(format!("{}'s spawn weight {}", name, problem));
+        }
+        if monster.min_depth < 1 {
+            problems.push(format!("{} has a min depth below 1", name));
+        }
+        if monster.max_depth.is_some_and(|max_depth| max_depth < monster.min_depth) {
+            problems.push(format!("{} has a max depth shallower than its min depth", name));
+        }
+    }
+
+    problems
+}
+
+fn validate_items(items: &[ItemTemplate]) -> Vec<String> {
+    let mut problems = vec![];
+    if items.is_empty() {
+        problems.push("there are no items".to_string());
+    }
+
+    for (idx, item) in items.iter().enumerate() {
+        let name = if item.name.is_empty() { format!("item {}", idx + 1) } else { item.name.clone() };
+        if item.name.is_empty() {
+            problems.push(format!("{} has no name", name));
+        }
+        if items[..idx].iter().any(|other| other.name == item.name) {
+            problems.push(format!("{} is defined more than once", name));
+        }
+        if let Some(problem) = item.spawn_weight.problems() {
+            problems.push(format!("{}'s spawn weight {}", name, problem));
+        }
+
+        match (&item.effect, &item.equipment) {
+            (Some(_), Some(_)) => problems.push(format!("{} can't have an effect and be equipment", name)),
+            (None, None) => problems.push(format!("{} needs an effect or to be equipment", name)),
+            _ => {}
+        }
+
+        let numbers_ok = match item.effect {
+            Some(Item::Heal { amount }) => amount > 0,
+            Some(Item::Lightning { damage, range }) => damage > 0 && range > 0,
+            Some(Item::Fireball { damage, radius }) => damage > 0 && radius >= 0,
+            Some(Item::Confuse { range, turns }) => range > 0 && turns > 0,
+            Some(Item::Status { effect, turns, strength }) => effect_ok(effect, turns, strength),
+            None => true,
+        };
+        if !numbers_ok {
+            problems.push(format!("{} has an effect that wouldn't do anything", name));
+        }
+        if item.equipment.as_ref().is_some_and(|eq| eq.power == 0 && eq.defence == 0 && eq.max_hp == 0) {
+            problems.push(format!("{} doesn't give any bonuses", name));
+        }
+    }
+
+    problems
+}
+
+fn validate_traps(traps: &[TrapTemplate]) -> Vec<String> {
+    let mut problems = vec![];
+    for (idx, trap) in traps.iter().enumerate() {
+        let name = if trap.name.is_empty() { format!("trap {}", idx + 1) } else { trap.name.clone() };
+        if trap.name.is_empty() {
+            problems.push(format!("{} has no name", name));
+        }
+        if traps[..idx].iter().any(|other| other.name == trap.name) {
+            problems.push(format!("{} is defined more than once", name));
+        }
+        if let Some(problem) = trap.spawn_weight.problems() {
+            problems.push(format!("{}'s spawn weight {}", name, problem));
+        }
+        if let Some(problem) = inflicts_problems(&trap.inflicts) {
+            problems.push(format!("{} {}", name, problem));
+        }
+    }
+    problems
+}
+
+// Vaults can only use monsters, items and traps from the other files, so those get checked first
+fn validate_vaults(vaults: &[VaultTemplate], monsters: &[MonsterTemplate], items: &[ItemTemplate], traps: &[TrapTemplate]) -> Vec<String> {
+    let mut problems = vec![];
+    for (idx, vault) in vaults.iter().enumerate() {
+        let name = if vault.name.is_empty() { format!("vault {}", idx + 1) } else { vault.name.clone() };
+        if vault.name.is_empty() {
+            problems.push(format!("{} has no name", name));
+        }
+        if vaults[..idx].iter().any(|other| other.name == vault.name) {
+            problems.push(format!("{} is defined more than once", name));
+        }
+        if let Some(problem) = vault.spawn_weight.problems() {
+            problems.push(format!("{}'s spawn weight {}", name, problem));
+        }
+
+        if vault.rows.is_empty() || vault.width() == 0 {
+            problems.push(format!("{} has nothing in it", name));
+            continue;
+        }
+        if vault.rows.iter().any(|row| row.chars().count() as i32 != vault.width()) {
+            problems.push(format!("{} has rows of different lengths", name));
+        }
+        // it needs a tile of rock all round it as well, inside the edge of the map
+        if vault.width() > MAP_WIDTH - 4 || vault.height() > MAP_HEIGHT - 4 {
+            problems.push(format!("{} is too big for the map", name));
+        }
+        let mut unknown: Vec<_> = vault.cells().map(|(_, _, chr)| chr)
+            .filter(|&chr| !matches!(chr, '#' | '.' | ' ') && !vault.legend.contains_key(&chr))
+            .collect();
+        unknown.sort();
+        unknown.dedup();
+        for chr in unknown {
+            problems.push(format!("{} uses '{}' but it's not in the legend", name, chr));
+        }
+        if !vault.cells().any(|(x, y, chr)| vault.on_edge(x, y) && vault.is_floor(chr)) {
+            problems.push(format!("{} has no way in - it needs some floor on its edge", name));
+        }
+
+        for (chr, cell) in &vault.legend {
+            if cell.monster.as_ref().is_some_and(|monster| !monsters.iter().any(|m| &m.name == monster)) {
+                problems.push(format!("{}'s '{}' is a monster that doesn't exist", name, chr));
+            }
+            if cell.item.as_ref().is_some_and(|item| !items.iter().any(|i| &i.name == item)) {
+                problems.push(format!("{}'s '{}' is an item that doesn't exist", name, chr));
+            }
+            if cell.trap.as_ref().is_some_and(|trap| !traps.iter().any(|t| &t.name == trap)) {
+                problems.push(format!("{}'s '{}' is a trap that doesn't exist", name, chr));
+            }
+        }
+    }
+    problems
+}
+
+// Poison and regeneration need a strength to do anything, and everything needs to last at least a turn
+fn effect_ok(effect: Effect, turns: i32, strength: i32) -> bool {
+    match effect {
+        Effect::Poisoned | Effect::Regenerating => turns > 0 && strength > 0,
+        _ => turns > 0,
+    }
+}
+
+fn inflicts_problems(inflicts: &Inflicts) -> Option<&'static str> {
+    if !effect_ok(inflicts.effect, inflicts.turns, inflicts.strength) {
+        Some("has an effect that wouldn't do anything")
+    } else if inflicts.chance == 0 || inflicts.chance > 100 {
+        Some("needs a chance between 1 and 100")
+    } else {
+        None
+    }
+}
+
+fn validate_dungeon(dungeon: &DungeonSettings, monsters: &[MonsterTemplate], items: &[ItemTemplate]) -> Vec<String> {
+    let mut problems = vec![];
+    if dungeon.generators.is_empty() {
+        problems.push("there are no generators".to_string());
+    }
+    for choice in &dungeon.generators {
+        if let Some(problem) = choice.weight.problems() {
+            problems.push(format!("the {:?} generator's weight {}", choice.generator, problem));
+        }
+    }
+    if let Some(problem) = dungeon.max_room_monsters.problems() {
+        problems.push(format!("max_room_monsters {}", problem));
+    }
+    if let Some(problem) = dungeon.max_room_items.problems() {
+        problems.push(format!("max_room_items {}", problem));
+    }
+    if let Some(problem) = dungeon.max_room_traps.problems() {
+        problems.push(format!("max_room_traps {}", problem));
+    }
+    if let Some(problem) = dungeon.max_vaults.problems() {
+        problems.push(format!("max_vaults {}", problem));
+    }
+    if dungeon.final_depth < 1 {
+        problems.push("the final depth is above depth 1".to_string());
+    }
+    if !monsters.iter().any(|monster| monster.name == dungeon.boss) {
+        problems.push(format!("the boss {} isn't one of the monsters", dungeon.boss));
+    }
+    if !items.iter().any(|item| item.name == dungeon.artefact) {
+        problems.push(format!("the artefact {} isn't one of the items", dungeon.artefact));
+    }
+    problems
+}
@@ -0,0 +1,197 @@
+// Dijkstra maps: how far every tile on the map is from the nearest goal. Anything can roll downhill on one to reach a
+// goal by the cheapest route - handy for auto-explore (the goals are every unexplored tile) or travelling to the
+// stairs - and a map built from the player's position can be turned inside out so monsters can run away sensibly.
+// Like A*, moves go in all eight directions and walls can never be crossed.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::game::Map;
+use crate::pathfinding::{Grid, NEIGHBOURS};
+
+const UNREACHABLE: i32 = i32::MAX;
+
+// Fleeing maps multiply the distances by this (as a fraction) before settling them again. Going further than -1 is
+// what makes fleeing things head for open space rather than backing into the nearest corner.
+const FLEE_FACTOR: (i32, i32) = (-6, 5);
+
+pub struct DijkstraMap {
+    grid: Grid,
+    values: Vec<i32>,
+}
+
+impl DijkstraMap {
+    // Every goal starts at 0. `cost` says what it costs to step onto a (non wall) tile, or None to keep out of it
+    pub fn new(map: &Map, goals: &[(i32, i32)], cost: impl Fn(i32, i32) -> Option<i32>) -> Self {
+        let weighted: Vec<_> = goals.iter().map(|&goal| (goal, 0)).collect();
+        Self::from_weighted_goals(map, &weighted, cost)
+    }
+
+    // Goals can start at different values, so that some are more tempting than others
+    pub fn from_weighted_goals(map: &Map, goals: &[((i32, i32), i32)], cost: impl Fn(i32, i32) -> Option<i32>) -> Self {
+        let grid = Grid::of(map);
+        let mut dijkstra = DijkstraMap { grid, values: vec![UNREACHABLE; grid.tiles()] };
+
+        // BinaryHeap pops the biggest first, so wrap it up to get the lowest value
+        let mut open = BinaryHeap::new();
+        for &((x, y), value) in goals {
+            if dijkstra.grid.in_bounds(x, y) && !map[x as usize][y as usize].blocked && value < dijkstra.values[dijkstra.grid.index(x, y)] {
+                let idx = dijkstra.grid.index(x, y);
+                dijkstra.values[idx] = value;
+                open.push(Reverse((value, (x, y))));
+            }
+        }
+
+        while let Some(Reverse((value, (x, y)))) = open.pop() {
+            // already found something lower since this was queued
+            if value > dijkstra.values[dijkstra.grid.index(x, y)] {
+                continue;
+            }
+
+            for (dx, dy) in NEIGHBOURS {
+                let (nx, ny) = (x + dx, y + dy);
+                if !dijkstra.grid.in_bounds(nx, ny) || map[nx as usize][ny as usize].blocked {
+                    continue;
+                }
+                if let Some(step_cost) = cost(nx, ny) {
+                    let next_value = value + step_cost;
+                    let idx = dijkstra.grid.index(nx, ny);
+                    if next_value < dijkstra.values[idx] {
+                        dijkstra.values[idx] = next_value;
+                        open.push(Reverse((next_value, (nx, ny))));
+                    }
+                }
+            }
+        }
+
+        dijkstra
+    }
+
+    // Turn a map of distances to something into a map for getting away from it. Rolling downhill on the result
+    // leads away from the original goals, preferring escape routes that keep going over dead ends.
+    // Rather than dividing (and rounding away the difference at short range) the costs get multiplied up instead, so
+    // the values on a fleeing map are only good for comparing with each other.
+    pub fn flee(&self, map: &Map, cost: impl Fn(i32, i32) -> Option<i32>) -> Self {
+        let mut goals = vec![];
+        for x in 0..self.grid.width {
+            for y in 0..self.grid.height {
+                if let Some(value) = self.value(x, y) {
+                    goals.push(((x, y), value * FLEE_FACTOR.0));
+                }
+            }
+        }
+        Self::from_weighted_goals(map, &goals, |x, y| cost(x, y).map(|step_cost| step_cost * FLEE_FACTOR.1))
+    }
+
+    // How far the tile is from the nearest goal, or None if no goal can be reached from it
+    pub fn value(&self, x: i32, y: i32) -> Option<i32> {
+        if !self.grid.in_bounds(x, y) {
+            return None;
+        }
+        Some(self.values[self.grid.index(x, y)]).filter(|&value| value != UNREACHABLE)
+    }
+
+    // The neighbouring tile that gets closest to a goal, if any of them are an improvement on where we are.
+    // `can_enter` rules out tiles that are taken right now (e.g. by a monster).
+    pub fn best_step(&self, x: i32, y: i32, can_enter: impl Fn(i32, i32) -> bool) -> Option<(i32, i32)> {
+        let mut best = None;
+        let mut best_value = self.value(x, y)?;
+        for (dx, dy) in NEIGHBOURS {
+            let (nx, ny) = (x + dx, y + dy);
+            if let Some(value) = self.value(nx, ny) {
+                if value < best_value && can_enter(nx, ny) {
+                    best = Some((nx, ny));
+                    best_value = value;
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map_from;
+
+    #[test]
+    fn distances_are_to_the_nearest_goal() {
+        let map = map_from(&["........."]);
+        let dijkstra = DijkstraMap::new(&map, &[(0, 0), (8, 0)], |_, _| Some(1));
+        assert_eq!(dijkstra.value(0, 0), Some(0));
+        assert_eq!(dijkstra.value(2, 0), Some(2));
+        assert_eq!(dijkstra.value(4, 0), Some(4));
+        assert_eq!(dijkstra.value(6, 0), Some(2));
+        assert_eq!(dijkstra.value(9, 0), None);
+    }
+
+    #[test]
+    fn diagonal_steps_cost_the_same_as_straight_ones() {
+        let map = map_from(&[".....", ".....", "....."]);
+        let dijkstra = DijkstraMap::new(&map, &[(0, 0)], |_, _| Some(1));
+        assert_eq!(dijkstra.value(2, 2), Some(2));
+        assert_eq!(dijkstra.value(4, 1), Some(4));
+    }
+
+    #[test]
+    fn costs_weigh_tiles_and_none_keeps_out() {
+        let map = map_from(&["......"]);
+        let weighted = DijkstraMap::new(&map, &[(0, 0)], |x, _| Some(if x == 2 { 5 } else { 1 }));
+        assert_eq!(weighted.value(1, 0), Some(1));
+        assert_eq!(weighted.value(2, 0), Some(6));
+        assert_eq!(weighted.value(5, 0), Some(9));
+
+        let blocked = DijkstraMap::new(&map, &[(0, 0)], |x, _| (x != 2).then_some(1));
+        assert_eq!(blocked.value(1, 0), Some(1));
+        assert_eq!(blocked.value(2, 0), None);
+        assert_eq!(blocked.value(5, 0), None);
+    }
+
+    #[test]
+    fn expensive_tiles_get_walked_round() {
+        let map = map_from(&["#.#", "...", "...", "..."]);
+        // the middle column costs a lot, so it's cheaper to go down the side and only step in at the end
+        let dijkstra = DijkstraMap::new(&map, &[(1, 0)], |x, _| Some(if x == 1 { 10 } else { 1 }));
+        assert_eq!(dijkstra.value(1, 3), Some(12));
+        assert_eq!(dijkstra.value(0, 3), Some(3));
+    }
+
+    #[test]
+    fn walls_and_goals_in_walls_are_unreachable() {
+        let map = map_from(&["..#.."]);
+        let dijkstra = DijkstraMap::new(&map, &[(0, 0), (2, 0)], |_, _| Some(1));
+        assert_eq!(dijkstra.value(1, 0), Some(1));
+        assert_eq!(dijkstra.value(2, 0), None);
+        assert_eq!(dijkstra.value(3, 0), None);
+    }
+
+    #[test]
+    fn best_step_heads_downhill() {
+        let map = map_from(&["#####", "....."]);
+        let dijkstra = DijkstraMap::new(&map, &[(0, 1)], |_, _| Some(1));
+        assert_eq!(dijkstra.best_step(3, 1, |_, _| true), Some((2, 1)));
+        // nowhere better to go from the goal itself, and nowhere at all if the way is taken
+        assert_eq!(dijkstra.best_step(0, 1, |_, _| true), None);
+        assert_eq!(dijkstra.best_step(3, 1, |x, y| (x, y) != (2, 1)), None);
+        // or from somewhere no goal can be reached from
+        assert_eq!(dijkstra.best_step(3, 0, |_, _| true), None);
+    }
+
+    #[test]
+    fn fleeing_prefers_open_ground_to_a_dead_end() {
+        // the player is in the alcove at the top, right above the monster. Going left gets a step further away
+        // sooner but ends up in a corner; going right leads into a room.
+        let map = map_from(&[
+            "############",
+            "####.#######",
+            "#..........#",
+            "######.....#",
+            "######.....#",
+            "############",
+        ]);
+        let from_player = DijkstraMap::new(&map, &[(4, 1)], |_, _| Some(1));
+        assert_eq!(from_player.value(3, 2), from_player.value(5, 2));
+
+        let flee = from_player.flee(&map, |_, _| Some(1));
+        assert_eq!(flee.best_step(4, 2, |_, _| true), Some((5, 2)));
+        assert_eq!(flee.best_step(5, 2, |_, _| true).map(|(x, _)| x > 5), Some(true));
+    }
+}
@@ -0,0 +1,222 @@
+// Stable handles for things in the game world, and storage for their components. Removing one entity never moves any of
+// the others about, and a handle to something that's been removed stops working rather than quietly pointing at
+// whatever gets put in its slot next.
+use std::ops::{Index, IndexMut};
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntityId {
+    index: usize,
+    // bumped every time the slot is reused so old handles to it can be spotted
+    generation: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entities<T> {
+    slots: Vec<Slot<T>>,
+    // emptied slots waiting to be reused
+    free: Vec<usize>,
+}
+
+impl<T> Default for Entities<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Entities<T> {
+    pub fn new() -> Self {
+        Entities { slots: vec![], free: vec![] }
+    }
+
+    pub fn insert(&mut self, value: T) -> EntityId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.generation = slot.generation.wrapping_add(1);
+                slot.value = Some(value);
+                EntityId { index, generation: slot.generation }
+            },
+            None => {
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                EntityId { index: self.slots.len() - 1, generation: 0 }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index).filter(|slot| slot.generation == id.generation)?;
+        let value = slot.value.take()?;
+        self.free.push(id.index);
+        Some(value)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.slots.get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        self.slots.get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    // A copy of the current handles. Loop over this rather than `iter` when entities may come and go along the way.
+    pub fn ids(&self) -> Vec<EntityId> {
+        self.iter().map(|(id, _)| id).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)|
+            slot.value.as_ref().map(|value| (EntityId { index, generation: slot.generation }, value))
+        )
+    }
+}
+
+impl<T> Index<EntityId> for Entities<T> {
+    type Output = T;
+
+    fn index(&self, id: EntityId) -> &T {
+        self.get(id).expect("No entity with that handle - it has probably been removed")
+    }
+}
+
+impl<T> IndexMut<EntityId> for Entities<T> {
+    fn index_mut(&mut self, id: EntityId) -> &mut T {
+        self.get_mut(id).expect("No entity with that handle - it has probably been removed")
+    }
+}
+
+// One kind of component for any number of entities, stored by the entity's slot so looking one up is just an index.
+// The handle is kept alongside so a component left behind by an old entity can't be mistaken for a new one's.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Components<T> {
+    slots: Vec<Option<(EntityId, T)>>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Components<T> {
+    pub fn new() -> Self {
+        Components { slots: vec![] }
+    }
+
+    // Give an entity this component, handing back the one it had before (if any)
+    pub fn insert(&mut self, id: EntityId, value: T) -> Option<T> {
+        if self.slots.len() <= id.index {
+            self.slots.resize_with(id.index + 1, || None);
+        }
+        self.slots[id.index].replace((id, value))
+            .filter(|&(old_id, _)| old_id == id)
+            .map(|(_, old)| old)
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index)?;
+        if slot.as_ref().is_some_and(|&(slot_id, _)| slot_id == id) {
+            slot.take().map(|(_, value)| value)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        match self.slots.get(id.index) {
+            Some(Some((slot_id, value))) if *slot_id == id => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        match self.slots.get_mut(id.index) {
+            Some(Some((slot_id, value))) if *slot_id == id => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.get(id).is_some()
+    }
+
+    // A copy of the handles with this component. Loop over this when components may come and go along the way.
+    pub fn ids(&self) -> Vec<EntityId> {
+        self.iter().map(|(id, _)| id).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.slots.iter().filter_map(|slot| slot.as_ref().map(|(id, value)| (*id, value)))
+    }
+}
+
+impl<T> Index<EntityId> for Components<T> {
+    type Output = T;
+
+    fn index(&self, id: EntityId) -> &T {
+        self.get(id).expect("The entity doesn't have that component")
+    }
+}
+
+impl<T> IndexMut<EntityId> for Components<T> {
+    fn index_mut(&mut self, id: EntityId) -> &mut T {
+        self.get_mut(id).expect("The entity doesn't have that component")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_removed_entity_stays_gone_when_its_slot_is_reused() {
+        let mut entities = Entities::new();
+        let old = entities.insert("orc");
+        let other = entities.insert("troll");
+        assert_eq!(entities.remove(old), Some("orc"));
+        assert_eq!(entities.get(old), None);
+
+        let new = entities.insert("potion");
+        // it went in the old one's slot, but the old handle doesn't see it
+        assert_eq!(new.index, old.index);
+        assert_ne!(new, old);
+        assert_eq!(entities.get(old), None);
+        assert_eq!(entities.get_mut(old), None);
+        assert_eq!(entities.remove(old), None);
+        assert_eq!(entities.get(new), Some(&"potion"));
+        assert_eq!(entities.get(other), Some(&"troll"));
+        assert_eq!(entities.ids(), vec![new, other]);
+    }
+
+    #[test]
+    fn components_ignore_stale_handles() {
+        let mut entities = Entities::new();
+        let mut hp = Components::new();
+        let old = entities.insert("orc");
+        assert_eq!(hp.insert(old, 10), None);
+        assert_eq!(hp.insert(old, 8), Some(10));
+
+        // the orc goes without its component being tidied up, and something else takes its slot
+        entities.remove(old);
+        let new = entities.insert("troll");
+        assert!(!hp.contains(new));
+        assert_eq!(hp.get(new), None);
+        // so the troll doesn't get handed back the orc's hp
+        assert_eq!(hp.insert(new, 16), None);
+        assert_eq!(hp.get(new), Some(&16));
+        assert_eq!(hp.get(old), None);
+        assert_eq!(hp.remove(old), None);
+        assert_eq!(hp.ids(), vec![new]);
+    }
+}
@@ -0,0 +1,153 @@
+// Field of view by recursive shadowcasting, worked out straight from the tile map. Doing it here rather than with
+// libtcod's FOV map keeps the game core free of anything that needs a C library. The view is a circle of the given
+// radius, and walls that block the view are seen themselves (so room edges light up as you walk in).
+use crate::game::Map;
+
+// how each of the eight octants maps onto the map - (xx, xy, yx, yy) multipliers for the octant's (dx, dy)
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1), (0, 1, 1, 0), (0, -1, 1, 0), (-1, 0, 0, 1),
+    (-1, 0, 0, -1), (0, -1, -1, 0), (0, 1, -1, 0), (1, 0, 0, -1),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Fov {
+    width: i32,
+    height: i32,
+    visible: Vec<bool>,
+}
+
+impl Fov {
+    pub fn new(width: i32, height: i32) -> Self {
+        Fov { width, height, visible: vec![false; (width * height) as usize] }
+    }
+
+    // Work out everything that can be seen from (x, y), forgetting whatever could be seen before
+    pub fn compute(&mut self, map: &Map, x: i32, y: i32, radius: i32) {
+        self.visible.fill(false);
+        if !self.in_bounds(x, y) {
+            return;
+        }
+        let idx = self.index(x, y);
+        self.visible[idx] = true;
+        for octant in OCTANTS {
+            self.cast_light(map, (x, y), 1, 1.0, 0.0, radius, octant);
+        }
+    }
+
+    pub fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.visible[self.index(x, y)]
+    }
+
+    // Scan one octant row by row outwards, between the `start` and `end` slopes. Anything that blocks sight splits
+    // the scan - the part before it carries on in a recursive call and the part after picks up once it's past.
+    #[allow(clippy::too_many_arguments)]
+    fn cast_light(&mut self, map: &Map, (cx, cy): (i32, i32), row: i32, mut start: f32, end: f32, radius: i32, (xx, xy, yx, yy): (i32, i32, i32, i32)) {
+        if start < end {
+            return;
+        }
+        let mut new_start = 0.0;
+        for distance in row..=radius {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
+                }
+
+                let (x, y) = (cx + dx * xx + dy * xy, cy + dx * yx + dy * yy);
+                if dx * dx + dy * dy <= radius * radius && self.in_bounds(x, y) {
+                    let idx = self.index(x, y);
+                    self.visible[idx] = true;
+                }
+
+                // off the edge of the map counts as solid
+                let opaque = !self.in_bounds(x, y) || map[x as usize][y as usize].block_sight;
+                if blocked {
+                    if opaque {
+                        new_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if opaque && distance < radius {
+                    blocked = true;
+                    self.cast_light(map, (cx, cy), distance + 1, start, left_slope, radius, (xx, xy, yx, yy));
+                    new_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (x * self.height + y) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Tile;
+
+    // An open floor with walls at the given spots
+    fn map_with_walls(width: i32, height: i32, walls: &[(i32, i32)]) -> Map {
+        let mut map = vec![vec![Tile::empty(); height as usize]; width as usize];
+        for &(x, y) in walls {
+            map[x as usize][y as usize] = Tile::wall();
+        }
+        map
+    }
+
+    #[test]
+    fn open_ground_is_seen_out_to_the_radius() {
+        let map = map_with_walls(21, 21, &[]);
+        let mut fov = Fov::new(21, 21);
+        fov.compute(&map, 10, 10, 5);
+        assert!(fov.is_in_fov(10, 10));
+        assert!(fov.is_in_fov(15, 10));
+        assert!(fov.is_in_fov(13, 14));
+        assert!(!fov.is_in_fov(16, 10));
+        // the corners of the square are outside the circle
+        assert!(!fov.is_in_fov(15, 15));
+    }
+
+    #[test]
+    fn walls_are_seen_but_hide_what_is_behind_them() {
+        let walls: Vec<_> = (0..9).map(|y| (5, y)).collect();
+        let map = map_with_walls(9, 9, &walls);
+        let mut fov = Fov::new(9, 9);
+        fov.compute(&map, 2, 4, 10);
+        assert!(fov.is_in_fov(5, 4));
+        assert!(fov.is_in_fov(5, 2));
+        assert!(!fov.is_in_fov(6, 4));
+        assert!(!fov.is_in_fov(8, 0));
+    }
+
+    #[test]
+    fn a_pillar_casts_a_shadow() {
+        let map = map_with_walls(15, 5, &[(4, 2)]);
+        let mut fov = Fov::new(15, 5);
+        fov.compute(&map, 2, 2, 20);
+        assert!(fov.is_in_fov(4, 2));
+        assert!(!fov.is_in_fov(8, 2));
+        assert!(fov.is_in_fov(8, 0));
+    }
+
+    #[test]
+    fn nothing_is_seen_from_off_the_map() {
+        let map = map_with_walls(5, 5, &[]);
+        let mut fov = Fov::new(5, 5);
+        fov.compute(&map, -1, 2, 10);
+        assert!(!fov.is_in_fov(0, 2));
+    }
+}
@@ -0,0 +1,597 @@
+// Everything the player sees and touches that isn't tied to a particular window system - menus, targeting,
+// key bindings and the game loop itself. A front end only has to supply input events and a `Renderer`.
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use crate::colors::*;
+use crate::data::GameData;
+use crate::entity::EntityId;
+use crate::game::*;
+use crate::highscores::*;
+use crate::morgue::{morgue_stem, write_morgue, write_morgue_replay};
+use crate::render::*;
+use crate::replay::*;
+use crate::world::*;
+
+const INVENTORY_WIDTH: i32 = 50;
+const LEVEL_SCREEN_WIDTH: i32 = 40;
+const CHARACTER_SCREEN_WIDTH: i32 = 30;
+const HIGH_SCORE_SCREEN_WIDTH: i32 = 78;
+const ERROR_SCREEN_WIDTH: i32 = 78;
+const MAX_NAME_LENGTH: usize = 15;
+
+const TARGET_COLOUR: Color = Color { r: 255, g: 255, b: 255 };
+
+// how long each command stays on screen when watching a replay, and how many to skip through per frame when fast forwarding
+const REPLAY_STEP_TIME: Duration = Duration::from_millis(200);
+const FAST_FORWARD_STEPS: usize = 10;
+
+// The keys the game cares about, whatever keyboard they came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    // the middle of the number pad - stand still
+    Centre,
+    Enter,
+    Escape,
+    Backspace,
+    Char(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Key(Key),
+    Mouse { x: i32, y: i32, left_click: bool, right_click: bool },
+}
+
+pub trait Frontend {
+    fn renderer(&mut self) -> &mut dyn Renderer;
+    // check for input without blocking. Front ends can use this to limit the frame rate too.
+    fn poll_event(&mut self) -> Option<InputEvent>;
+    fn wait_for_key(&mut self) -> Key;
+    fn is_closed(&self) -> bool;
+    // the last place the mouse was seen (in screen cells)
+    fn mouse_pos(&self) -> (i32, i32);
+
+    // anything the front end wants behind the main menu
+    fn draw_menu_background(&mut self) {}
+}
+
+fn render<F: Frontend>(f: &mut F, game: &Game, world: &World, look: (i32, i32)) {
+    render_all(f.renderer(), game, world, look);
+}
+
+fn handle_keys<F: Frontend>(key: Option<Key>, f: &mut F, game: &mut Game, world: &mut World) -> PlayerAction {
+    use Key::*;
+    use PlayerAction::*;
+
+    let key = match key {
+        Some(key) => key,
+        None => return DidntTakeTurn,
+    };
+
+    let player_alive = world.is_alive(game.player);
+
+    // there's nothing to do while paralysed but wait for it to wear off, so any key passes the turn
+    if player_alive && key != Escape && world.has_effect(game.player, Effect::Paralysed) {
+        return play_turn(PlayerCommand::Wait, game, world);
+    }
+
+    let command = match (key, player_alive) {
+        (Up, true) => PlayerCommand::Move(0, -1),
+        (Down, true) => PlayerCommand::Move(0, 1),
+        (Left, true) => PlayerCommand::Move(-1, 0),
+        (Right, true) => PlayerCommand::Move(1, 0),
+        (Home, true) => PlayerCommand::Move(-1, -1),
+        (PageUp, true) => PlayerCommand::Move(1, -1),
+        (End, true) => PlayerCommand::Move(-1, 1),
+        (PageDown, true) => PlayerCommand::Move(1, 1),
+        (Centre, true) => {
+            // stand still
+            PlayerCommand::Wait
+        },
+        (Char('g'), true) => PlayerCommand::PickUp,
+        (Char('i'), true) => {
+            let inv_idx = inventory_menu(&game.inventory, world, "Select item to use", f);
+            match inv_idx {
+                Some(inv_id) => {
+                    let target = choose_target(inv_id, f, game, world);
+                    PlayerCommand::UseItem { inv_id, target }
+                },
+                None => return DidntTakeTurn,
+            }
+        },
+        (Char('d'), true) => {
+            let inv_idx = inventory_menu(&game.inventory, world, "Select item to drop", f);
+            match inv_idx {
+                Some(inv_idx) => PlayerCommand::DropItem(inv_idx),
+                None => return DidntTakeTurn,
+            }
+        },
+        (Char('e'), true) => {
+            let inv_idx = inventory_menu(&game.inventory, world, "Select item to equip or take off", f);
+            match inv_idx {
+                Some(inv_idx) => {
+                    let equipped = world.equipment.get(game.inventory[inv_idx]).is_some_and(|eq| eq.equipped_by.is_some());
+                    if equipped {
+                        PlayerCommand::Unequip(inv_idx)
+                    } else {
+                        PlayerCommand::Equip(inv_idx)
+                    }
+                },
+                None => return DidntTakeTurn,
+            }
+        },
+        (Char('>'), true) => PlayerCommand::Descend,
+        (Char('<'), true) => PlayerCommand::Ascend,
+        (Char('x'), true) => return repeat_command(PlayerCommand::Explore, f, game, world),
+        (Char('t'), true) => return repeat_command(PlayerCommand::TravelToStairs, f, game, world),
+        (Char('c'), true) => {
+            if let Some(info) = character_info(game, world) {
+                msgbox(&format!("Character Information\n\n{}", info), CHARACTER_SCREEN_WIDTH, f);
+            }
+            return DidntTakeTurn;
+        }
+        (Escape, _) => return Exit,
+        _ => return DidntTakeTurn
+    };
+
+    play_turn(command, game, world)
+}
+
+// Keep giving the same command, drawing each step, until the game says it's done (e.g. something came into view),
+// something disturbs the player (like a trap going off), a level up needs dealing with or the player presses a key
+// to stop
+fn repeat_command<F: Frontend>(command: PlayerCommand, f: &mut F, game: &mut Game, world: &mut World) -> PlayerAction {
+    let first_action = play_turn(command, game, world);
+    let mut action = first_action;
+    while action == PlayerAction::TookTurn && !game.disturbed && !level_up_due(game, world) && !f.is_closed() {
+        let look = f.mouse_pos();
+        render(f, game, world, look);
+        f.renderer().present();
+        if let Some(InputEvent::Key(_)) = f.poll_event() {
+            break;
+        }
+        action = play_turn(command, game, world);
+    }
+    first_action
+}
+
+// Ask the player where they want to aim the item (if it needs aiming at all)
+fn choose_target<F: Frontend>(inv_id: usize, f: &mut F, game: &mut Game, world: &World) -> Option<(i32, i32)> {
+    match item_targeting(inv_id, game, world) {
+        Targeting::None => None,
+        Targeting::Tile { max_range } => {
+            game.messages.add("Pick a target tile with the mouse or the movement keys and Enter. Right-click or Escape cancels", LIGHT_CYAN);
+            target_tile(f, game, world, max_range)
+        },
+        Targeting::Monster { max_range } => {
+            game.messages.add("Pick an enemy with the mouse or the movement keys and Enter. Right-click or Escape cancels", LIGHT_CYAN);
+            target_monster(f, game, world, max_range)
+        },
+    }
+}
+
+// Let the player pick a tile. The cursor follows the mouse and can be nudged about with the movement keys
+// for anyone without one (e.g. over ssh)
+fn target_tile<F: Frontend>(f: &mut F, game: &Game, world: &World, max_range: Option<f32>) -> Option<(i32, i32)> {
+    let player_pos = world.positions[game.player];
+    let mut cursor = (player_pos.x, player_pos.y);
+    loop {
+        let mut picked = false;
+        let mut cancelled = false;
+        match f.poll_event() {
+            Some(InputEvent::Mouse { x, y, left_click, right_click }) => {
+                cursor = (x, y);
+                picked = left_click;
+                cancelled = right_click;
+            },
+            Some(InputEvent::Key(key)) => {
+                let (dx, dy) = match key {
+                    Key::Up => (0, -1),
+                    Key::Down => (0, 1),
+                    Key::Left => (-1, 0),
+                    Key::Right => (1, 0),
+                    Key::Home => (-1, -1),
+                    Key::PageUp => (1, -1),
+                    Key::End => (-1, 1),
+                    Key::PageDown => (1, 1),
+                    _ => (0, 0),
+                };
+                cursor = (cursor.0 + dx, cursor.1 + dy);
+                picked = key == Key::Enter || key == Key::Centre;
+                cancelled = key == Key::Escape;
+            },
+            None => {}
+        }
+        // keep the cursor on the map, wherever the mouse or the keys try to take it
+        cursor = (cursor.0.clamp(0, MAP_WIDTH - 1), cursor.1.clamp(0, MAP_HEIGHT - 1));
+
+        render(f, game, world, cursor);
+        f.renderer().draw_background(cursor.0, cursor.1, TARGET_COLOUR);
+        f.renderer().present();
+
+        let (x, y) = cursor;
+        let in_fov = (0..MAP_WIDTH).contains(&x) && (0..MAP_HEIGHT).contains(&y) && game.fov.is_in_fov(x, y);
+        let in_range = max_range.is_none_or(|rng| player_pos.distance(x, y) <= rng);
+        if picked && in_fov && in_range {
+            return Some((x, y));
+        }
+        if cancelled || f.is_closed() {
+            return None
+        }
+    }
+}
+
+fn target_monster<F: Frontend>(f: &mut F, game: &Game, world: &World, max_range: Option<f32>) -> Option<(i32, i32)> {
+    loop {
+        match target_tile(f, game, world, max_range) {
+            Some((x, y)) => {
+                if monster_at(x, y, game, world).is_some() {
+                    return Some((x, y));
+                }
+            },
+            None => return None
+        }
+    }
+}
+
+// Level ups need the player to pick a stat so keep showing the menu until they do
+fn level_up<F: Frontend>(f: &mut F, game: &mut Game, world: &mut World) {
+    if !level_up_due(game, world) {
+        return;
+    }
+
+    let fighter = world.fighters[game.player];
+    let mut choice = None;
+    while choice.is_none() {
+        // closing the game doesn't count as a choice - it'll be asked again when the save's loaded
+        if f.is_closed() {
+            return;
+        }
+        choice = menu(
+            "Level up - choose a stat to raise:\n",
+            &[
+                format!("Constitution: (+20 HP from {})", fighter.max_hp),
+                format!("Strength: (+1 attacke from {})", fighter.power),
+                format!("Agility: (+1 defence from {})", fighter.defence),
+            ],
+            LEVEL_SCREEN_WIDTH,
+            f
+        );
+    }
+
+    let stat = match choice.unwrap() {
+        0 => Stat::Constitution,
+        1 => Stat::Strength,
+        2 => Stat::Agility,
+        _ => unreachable!(),
+    };
+    play_turn(PlayerCommand::LevelUp(stat), game, world);
+}
+
+pub fn menu<F: Frontend, T: AsRef<str>>(header: &str, options: &[T], width: i32, f: &mut F) -> Option<usize> {
+    draw_menu(f.renderer(), header, options, width);
+    f.renderer().present();
+    match f.wait_for_key() {
+        Key::Char(chr) if chr.is_ascii_alphabetic() => {
+            let index = chr.to_ascii_lowercase() as usize - 'a' as usize;
+            if index < options.len() {
+                Some(index)
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+fn inventory_menu<F: Frontend>(inventory: &[EntityId], world: &World, header: &str, f: &mut F) -> Option<usize> {
+    let options = if inventory.is_empty() {
+        vec!["Inventory is empty".into()]
+    } else {
+        inventory.iter().map(|&item| match world.equipment.get(item) {
+            Some(eq) if eq.equipped_by.is_some() => format!("{} (on {})", world.name(item), eq.slot),
+            _ => world.name(item).to_string(),
+        }).collect()
+    };
+
+    let inv_idx = menu(header, &options, INVENTORY_WIDTH, f);
+    if !inventory.is_empty() {
+        inv_idx
+    } else {
+        None
+    }
+}
+
+pub fn msgbox<F: Frontend>(message: &str, width: i32, f: &mut F) {
+    let options: &[&str] = &[];
+    menu(message, options, width, f);
+}
+
+pub fn play_game<F: Frontend>(f: &mut F, game: &mut Game, world: &mut World) {
+    // It's a game; it needs a game loop
+    while !f.is_closed() {
+        let key = match f.poll_event() {
+            Some(InputEvent::Key(key)) => Some(key),
+            _ => None,
+        };
+
+        let look = f.mouse_pos();
+        render(f, game, world, look);
+        f.renderer().present();
+
+        let commands_played = game.commands.len();
+
+        // Check level up
+        level_up(f, game, world);
+
+        let action = handle_keys(key, f, game, world);
+
+        // keep the replay file up to date every turn so there's one to look at even if the game crashes.
+        // Not being able to write it isn't worth stopping the game over.
+        if game.commands.len() != commands_played {
+            save_replay(game, REPLAY_FILE).ok();
+        }
+
+        // the run's over one way or the other. The save goes so there's no carrying on from before the end, and it's
+        // back to the main menu
+        if game.won || !world.is_alive(game.player) {
+            render(f, game, world, look);
+            f.renderer().present();
+            // a run that can't be written up is still over
+            let stem = morgue_stem(game).ok();
+            let morgue = stem.as_ref().and_then(|stem| write_morgue(game, world, stem).ok());
+            let replay = stem.as_ref().and_then(|stem| write_morgue_replay(game, stem).ok());
+            game_over_screen(f, game, world, morgue, replay);
+            delete_save().ok();
+            record_high_score(f, game, world);
+            return;
+        }
+
+        if action == PlayerAction::Exit {
+            break;
+        }
+    }
+
+    // however the game got left - Escape, or the window closing (even in the middle of a menu) - it's still going
+    save_game(game, world).unwrap();
+}
+
+fn game_over_screen<F: Frontend>(f: &mut F, game: &Game, world: &World, morgue: Option<String>, replay: Option<String>) {
+    let headline = if game.won {
+        format!("You escaped with the {}!", game.data.dungeon.artefact)
+    } else {
+        format!(
+            "You died on dungeon level {}\n\nKilled by: {}",
+            game.depth, game.stats.killed_by.as_deref().unwrap_or("something")
+        )
+    };
+    let mut msg = format!("{}\n\n{}", headline, run_summary(game, world));
+    if let Some(path) = morgue {
+        msg += &format!("\n\nMorgue file: {}", path);
+    }
+    if let Some(path) = replay {
+        msg += &format!("\nReplay: {}", path);
+    }
+    msgbox(&msg, CHARACTER_SCREEN_WIDTH, f);
+}
+
+// What the character screen shows, which the morgue file has too
+pub fn character_info(game: &Game, world: &World) -> Option<String> {
+    let fighter = world.fighters.get(game.player)?;
+    let lvl = fighter.level;
+    let lvl_up_xp = LEVEL_UP_BASE + lvl * LEVEL_UP_FACTOR;
+    Some(format!(
+        "Level: {}
+Experience: {}
+Next Level: {}
+
+Max HP: {}
+Attack: {}.
+Defence: {}
+Speed: {}
+
+Seed: {}",
+        lvl, fighter.xp, lvl_up_xp, world.max_hp(game.player), world.power(game.player), world.defence(game.player),
+        world.speed(game.player), game.seed
+    ))
+}
+
+// How the run went, for the end of the game
+fn run_summary(game: &Game, world: &World) -> String {
+    let (level, xp) = world.fighters.get(game.player).map_or((0, 0), |fighter| (fighter.level, fighter.xp));
+    format!(
+        "Score: {}
+
+Character level: {}
+Experience: {}
+Deepest level: {}
+Monsters killed: {}
+Turns taken: {}
+
+Seed: {}",
+        final_score(game, world), level, xp, game.stats.deepest, game.stats.total_kills(), game.stats.turns, game.seed
+    )
+}
+
+// Give a finished run its place on the high score table if it's earned one, then show the table either way.
+// A table that can't be read or written just means this run doesn't get remembered - and one that can't be read is left
+// alone rather than written over, so the scores already on it aren't lost.
+fn record_high_score<F: Frontend>(f: &mut F, game: &Game, world: &World) {
+    let mut scores = match load_high_scores() {
+        Ok(scores) => scores,
+        Err(e) => {
+            msgbox(&format!("\nCouldn't read the high score table:\n{}\n", e), CHARACTER_SCREEN_WIDTH, f);
+            return;
+        }
+    };
+    let mut new_entry = None;
+    if makes_the_table(&scores, final_score(game, world)) {
+        let name = ask_name(f);
+        new_entry = add_high_score(&mut scores, high_score_entry(&name, game, world));
+        save_high_scores(&scores).ok();
+    }
+    high_score_screen(f, &scores, new_entry);
+}
+
+// Type in a name for the high score table. Enter finishes it and Escape leaves it blank
+fn ask_name<F: Frontend>(f: &mut F) -> String {
+    let mut name = String::new();
+    while !f.is_closed() {
+        // menus only fill in the background, so the rest of the line needs blanking out for backspace to show up
+        let blank = " ".repeat(MAX_NAME_LENGTH - name.chars().count());
+        let header = format!("You made the high score table!\n\nYour name: {}_{}", name, blank);
+        let options: &[&str] = &[];
+        draw_menu(f.renderer(), &header, options, CHARACTER_SCREEN_WIDTH);
+        f.renderer().present();
+        match f.wait_for_key() {
+            Key::Enter => break,
+            Key::Escape => {
+                name.clear();
+                break;
+            },
+            Key::Backspace => {
+                name.pop();
+            },
+            Key::Char(chr) if !chr.is_control() && name.chars().count() < MAX_NAME_LENGTH => name.push(chr),
+            _ => {},
+        }
+    }
+
+    match name.trim() {
+        "" => "Nameless".to_string(),
+        name => name.to_string(),
+    }
+}
+
+// `new_entry` is the row to point out, if the run that's just finished got on. Leading spaces get lost when the text
+// is wrapped so every row starts with its place to keep the columns lined up.
+fn high_score_screen<F: Frontend>(f: &mut F, scores: &[HighScore], new_entry: Option<usize>) {
+    let mut lines = vec!["High Scores".to_string(), String::new()];
+    if scores.is_empty() {
+        lines.push("Nobody's finished a run yet".to_string());
+    } else {
+        lines.push(format!("{:<4} {:<15} {:>6} Depth Level {:<10} Fate", "#", "Name", "Score", "Date"));
+    }
+    for (idx, entry) in scores.iter().enumerate() {
+        let marker = if new_entry == Some(idx) { ">" } else { "" };
+        let place = format!("{}{}.", marker, idx + 1);
+        let fate = entry.killed_by.as_ref().map_or("escaped".to_string(), |cause| format!("killed by {}", cause));
+        lines.push(format!(
+            "{:<4} {:<15} {:>6} {:>5} {:>5} {:<10} {}",
+            place, entry.name, entry.score, entry.depth, entry.level, entry.date, fate
+        ));
+    }
+    f.renderer().clear_screen();
+    msgbox(&lines.join("\n"), HIGH_SCORE_SCREEN_WIDTH, f);
+}
+
+// Watch a recorded run play itself. Space pauses, 's' steps one command while paused, 'f' toggles fast forward
+// and Escape stops watching.
+pub fn play_replay<F: Frontend>(f: &mut F, replay: &Replay, data: &Rc<GameData>) {
+    let (mut game, mut world) = match new_game(replay.seed, data.clone()) {
+        Ok(result) => result,
+        Err(e) => {
+            msgbox(&format!("\n{}\n", e), ERROR_SCREEN_WIDTH, f);
+            return;
+        }
+    };
+    let mut next = 0;
+    let mut paused = false;
+    let mut fast_forward = false;
+    let mut last_step = Instant::now();
+
+    while !f.is_closed() {
+        let mut steps = 0;
+        match f.poll_event() {
+            Some(InputEvent::Key(Key::Char(' '))) => paused = !paused,
+            Some(InputEvent::Key(Key::Char('s'))) if paused => steps = 1,
+            Some(InputEvent::Key(Key::Char('f'))) => fast_forward = !fast_forward,
+            Some(InputEvent::Key(Key::Escape)) => break,
+            _ => {}
+        }
+
+        if !paused {
+            if fast_forward {
+                steps = FAST_FORWARD_STEPS;
+            } else if last_step.elapsed() >= REPLAY_STEP_TIME {
+                steps = 1;
+            }
+        }
+
+        for &command in replay.commands.iter().skip(next).take(steps) {
+            play_turn(command, &mut game, &mut world);
+            next += 1;
+            last_step = Instant::now();
+        }
+
+        let state = if next == replay.commands.len() {
+            "Finished - Esc to quit"
+        } else if paused {
+            "Paused"
+        } else if fast_forward {
+            "Fast forward"
+        } else {
+            "Playing"
+        };
+
+        let look = f.mouse_pos();
+        render(f, &game, &world, look);
+        draw_replay_status(f.renderer(), next, replay.commands.len(), state);
+        f.renderer().present();
+    }
+}
+
+// `seed` fixes the dungeon for every new game started from here, otherwise each one gets a fresh random seed
+pub fn main_menu<F: Frontend>(f: &mut F, seed: Option<u64>, data: &Rc<GameData>) {
+    while !f.is_closed() {
+        f.renderer().clear_screen();
+        f.draw_menu_background();
+        draw_title(f.renderer());
+
+        let choices = &["Play a new game", "Continue previous game", "High scores", "Quit"];
+        let choice = menu("", choices, 27, f);
+
+        match choice {
+            Some(0) => {
+                // New game
+                match new_game(seed.unwrap_or_else(rand::random), data.clone()) {
+                    Ok((mut game, mut world)) => {
+                        play_game(f, &mut game, &mut world);
+                    },
+                    Err(e) => {
+                        msgbox(&format!("\n{}\n", e), ERROR_SCREEN_WIDTH, f);
+                        continue;
+                    }
+                }
+            },
+            Some(1) => {
+                match load_game(data.clone()) {
+                    Ok((mut game, mut world)) => {
+                        play_game(f, &mut game, &mut world);
+                    },
+                    Err(_e) => {
+                        msgbox("\nNo saved game to load\n", 24, f);
+                        continue;
+                    }
+                }
+            },
+            Some(2) => {
+                let scores = load_high_scores().unwrap_or_default();
+                high_score_screen(f, &scores, None);
+            },
+            Some(3) => {
+                // quit
+                break;
+            },
+            _ => {}
+        }
+    }
+}
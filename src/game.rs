@@ -0,0 +1,1586 @@
+// The game core. Everything in here is pure game state and rules - no windows, no consoles and no input handling.
+// Front ends turn whatever input they have into a `PlayerCommand` and hand it to `play_turn`, then draw the result.
+// Nothing in here needs libtcod - FOV and colours are our own (see fov.rs and colors.rs), so the core builds and runs
+// without the `tcod` feature.
+use std::cmp;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::io::Write;
+use std::rc::Rc;
+use serde::Deserialize;
+use serde::Serialize;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::colors::*;
+use crate::data::*;
+use crate::dijkstra::*;
+use crate::entity::*;
+use crate::fov::Fov;
+use crate::mapgen::*;
+use crate::pathfinding::*;
+use crate::world::*;
+
+pub const MAP_WIDTH: i32 = 80;
+pub const MAP_HEIGHT: i32 = 43;
+pub const MAX_INVENTORY: usize = 26;
+const SAVE_FILE: &str = "savegame";
+
+// what it costs a path to go through something that's in the way. High enough that monsters go round each other
+// when there's room, low enough that they'll still queue up behind one another in a corridor
+const PATH_BLOCKED_COST: i32 = 10;
+
+// Everything that acts builds up energy as time passes, `speed` a tick, and gets to act once it has ACTION_COST.
+// A normal speed creature gets one action for every one of the player's, one with double the speed gets two.
+pub const NORMAL_SPEED: i32 = 10;
+const ACTION_COST: i32 = 100;
+
+// what the staircases are called, which is how they get found again
+const STAIRS_DOWN: &str = "stairs down";
+const STAIRS_UP: &str = "stairs up";
+
+// how many goes a level gets at passing its checks before we give up on the whole thing
+const MAX_LEVEL_TRIES: i32 = 20;
+
+// cowardly monsters run for it once they're down to this fraction of their hit points
+const FLEE_HP_FRACTION: f32 = 0.5;
+
+const TORCH_RADIUS: i32 = 10;
+
+pub const LEVEL_UP_BASE: i32 = 200;
+pub const LEVEL_UP_FACTOR: i32 = 150;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerAction {
+    TookTurn,
+    DidntTakeTurn,
+    Exit,
+}
+
+// Everything the player can ask the game to do. Front ends build these from keys, menus and mouse clicks.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlayerCommand {
+    // move by the given delta, attacking anything that's in the way
+    Move(i32, i32),
+    Wait,
+    PickUp,
+    UseItem { inv_id: usize, target: Option<(i32, i32)> },
+    DropItem(usize),
+    Equip(usize),
+    Unequip(usize),
+    Descend,
+    Ascend,
+    LevelUp(Stat),
+    // one step towards the nearest unexplored part of the level. Front ends repeat these until one doesn't take a turn
+    Explore,
+    // one step along the known way to the stairs, repeated the same way
+    TravelToStairs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Stat {
+    Constitution,
+    Strength,
+    Agility,
+}
+
+// What a front end needs to ask the player for before an item can be used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Targeting {
+    None,
+    Tile { max_range: Option<f32> },
+    Monster { max_range: Option<f32> },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AI {
+    Basic,
+    // fights like a basic monster until it's badly hurt, then runs away from the player
+    Cowardly,
+}
+
+// Things that can be wrong (or right) with anything that takes turns, player included. Each one lasts a number of
+// the affected creature's own turns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Effect {
+    // stumbles about at random
+    Confused,
+    // loses `strength` hp every turn
+    Poisoned,
+    // gets `strength` hp back every turn
+    Regenerating,
+    // twice as fast
+    Hasted,
+    // half as fast
+    Slowed,
+    // can't see much of anything
+    Blinded,
+    // can't do anything at all
+    Paralysed,
+}
+
+impl Effect {
+    // how the creature is described while it's affected, e.g. "The orc is confused"
+    pub fn describe(self) -> &'static str {
+        match self {
+            Effect::Confused => "confused",
+            Effect::Poisoned => "poisoned",
+            Effect::Regenerating => "regenerating",
+            Effect::Hasted => "hasted",
+            Effect::Slowed => "slowed",
+            Effect::Blinded => "blinded",
+            Effect::Paralysed => "paralysed",
+        }
+    }
+
+    fn stacking(self) -> Stacking {
+        match self {
+            Effect::Poisoned => Stacking::Intensify,
+            Effect::Regenerating => Stacking::Extend,
+            _ => Stacking::Refresh,
+        }
+    }
+
+    // haste and slow cancel each other out
+    fn opposite(self) -> Option<Effect> {
+        match self {
+            Effect::Hasted => Some(Effect::Slowed),
+            Effect::Slowed => Some(Effect::Hasted),
+            _ => None,
+        }
+    }
+}
+
+// What happens when something gets an effect it already has
+enum Stacking {
+    // it lasts as long as the longer of the two
+    Refresh,
+    // the durations add up
+    Extend,
+    // the strengths add up and it lasts as long as the longer of the two
+    Intensify,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub effect: Effect,
+    pub turns: i32,
+    // how much it does each turn, for the effects that do anything each turn
+    pub strength: i32,
+}
+
+// A status effect that gets passed on to whoever's on the receiving end - a monster's poisonous bite, or a trap
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Inflicts {
+    pub effect: Effect,
+    pub turns: i32,
+    #[serde(default)]
+    pub strength: i32,
+    // percentage chance of it taking hold each time
+    #[serde(default = "always")]
+    pub chance: u32,
+}
+
+fn always() -> u32 {
+    100
+}
+
+// What an item does when it's used. The numbers come from the item's template in the data files.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Item {
+    Heal { amount: i32 },
+    // hits the closest monster within range
+    Lightning { damage: i32, range: i32 },
+    // hits everything within the radius of a tile the player picks
+    Fireball { damage: i32, radius: i32 },
+    Confuse { range: i32, turns: i32 },
+    // gives the player a status effect, e.g. a potion of haste
+    Status {
+        effect: Effect,
+        turns: i32,
+        #[serde(default)]
+        strength: i32,
+    },
+}
+
+// Where a piece of equipment goes. Two rings can be worn at once, everything else is one per slot
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    Weapon,
+    Shield,
+    Armour,
+    Helmet,
+    Ring,
+}
+
+impl Slot {
+    pub fn capacity(self) -> usize {
+        match self {
+            Slot::Ring => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Slot::Weapon => "weapon",
+            Slot::Shield => "shield",
+            Slot::Armour => "armour",
+            Slot::Helmet => "helmet",
+            Slot::Ring => "ring",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+enum UseResult {
+    UsedUp,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+}
+
+impl DeathCallback {
+    fn callback(self, id: EntityId, game: &mut Game, world: &mut World) {
+        use DeathCallback::*;
+        let cbk: fn(EntityId, &mut Game, &mut World) = match self {
+            Player => player_death,
+            Monster => monster_death,
+        };
+
+        cbk(id, game, world);
+    }
+}
+
+// type definitions
+pub type Map = Vec<Vec<Tile>>;
+// All the randomness in the game comes from one of these so a run can be reproduced from its seed
+pub type GameRng = ChaCha8Rng;
+
+// the game state
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    pub map: Map,
+    pub messages: Messages,
+    // carried items are still entities in the world, they just don't have a position
+    pub inventory: Vec<EntityId>,
+    pub player: EntityId,
+    pub depth: i32,
+    // every level the player has been to apart from the one they're on, by depth
+    #[serde(default)]
+    pub levels: BTreeMap<i32, Level>,
+    #[serde(default)]
+    pub stats: Stats,
+    // made it out of the dungeon with the artefact
+    #[serde(default)]
+    pub won: bool,
+    pub seed: u64,
+    // the generator's full state goes in the save so a loaded game carries on exactly where it left off
+    pub rng: GameRng,
+    // every command the player has given since the start, which together with the seed is enough to replay the run
+    pub commands: Vec<PlayerCommand>,
+    // the monster templates and so on come from the data files, not the save - load_game is handed them again
+    #[serde(skip)]
+    pub data: Rc<GameData>,
+    // what the player can see is worked out from the tile map so it never goes in the save - update_fov redoes it
+    #[serde(skip, default = "new_fov_map")]
+    pub fov: Fov,
+    // something happened this turn that should stop the player walking about on autopilot, e.g. a trap went off
+    #[serde(skip)]
+    pub disturbed: bool,
+    // what can see the player, which isn't the same thing once they're blinded - a blind player still stands out
+    #[serde(skip, default = "new_fov_map")]
+    pub sight: Fov,
+}
+
+fn new_fov_map() -> Fov {
+    Fov::new(MAP_WIDTH, MAP_HEIGHT)
+}
+
+// Running totals for the summary at the end of a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub turns: u32,
+    // how many of each monster the player has killed, by name
+    pub kills: BTreeMap<String, u32>,
+    pub deepest: i32,
+    // whatever finished the player off, e.g. "Orc" or "poison"
+    pub killed_by: Option<String>,
+}
+
+impl Stats {
+    pub fn total_kills(&self) -> u32 {
+        self.kills.values().sum()
+    }
+}
+
+// A level the player has left. Its monsters and items stay in the world the whole time, they just lose their
+// positions (which is what keeps them out of the way) until the player comes back and they get put back where they were
+#[derive(Serialize, Deserialize)]
+pub struct Level {
+    pub map: Map,
+    pub positions: Vec<(EntityId, Position)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Messages {
+    messages: Vec<(String, Color)>,
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        Self { messages: vec![] }
+    }
+
+    pub fn add<T: Into<String>>(&mut self, msg: T, colour: Color) {
+        self.messages.push((msg.into(), colour));
+    }
+
+    // creating a deque here essentially so let's have an iterator that can go both ways
+    // There's a bit of jiggery-pokery going on here. We have to iterate over a vec of different message types potentially.
+    // As we don't know exactly what the iterator looks like we just ask for a list of things that implement the double ended iterator trait
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(String, Color)> {
+        self.messages.iter()
+    }
+}
+
+// The stats here are the fighter's own - `World::power` and friends add on whatever it has equipped
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Fighter {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defence: i32,
+    pub power: i32,
+    // what the player has earned so far, or what a monster is worth when it's killed
+    pub xp: i32,
+    pub level: i32,
+    pub on_death: DeathCallback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub power_bonus: i32,
+    pub defence_bonus: i32,
+    pub max_hp_bonus: i32,
+    // who's wearing it, if anyone
+    pub equipped_by: Option<EntityId>,
+}
+
+// Anything that takes turns - the player and the monsters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    pub speed: i32,
+    // acts when this reaches ACTION_COST
+    pub energy: i32,
+}
+
+impl Actor {
+    pub fn new(speed: i32) -> Self {
+        Actor { speed, energy: 0 }
+    }
+}
+
+// tile definitions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Tile {
+    pub blocked: bool,
+    pub explored: bool,
+    pub block_sight: bool,
+}
+
+impl Tile {
+    pub fn empty() -> Self {
+        Tile { blocked: false, explored: false, block_sight: false }
+    }
+
+    pub fn wall() -> Self {
+        Tile { blocked: true, explored: false, block_sight: true }
+    }
+}
+
+// A map drawn row by row for tests, `#` for walls and anything else for floor
+#[cfg(test)]
+pub fn map_from(rows: &[&str]) -> Map {
+    let width = rows[0].len();
+    (0..width).map(|x| rows.iter().map(|row| {
+        if row.as_bytes()[x] == b'#' { Tile::wall() } else { Tile::empty() }
+    }).collect()).collect()
+}
+
+// Advance the game by one player command. If the command used up the player's turn then time passes until the
+// player is ready to act again, with the monsters acting whenever they're ready along the way.
+pub fn play_turn(command: PlayerCommand, game: &mut Game, world: &mut World) -> PlayerAction {
+    game.commands.push(command);
+    game.disturbed = false;
+    let action = player_act(command, game, world);
+
+    // the player may have moved or changed level so the monsters need to see the world as it is now
+    update_fov(game, world);
+
+    if action == PlayerAction::TookTurn {
+        game.stats.turns += 1;
+        spend_energy(game.player, world);
+        tick_effects(game.player, game, world);
+        pass_time(game, world);
+    }
+
+    action
+}
+
+fn player_act(command: PlayerCommand, game: &mut Game, world: &mut World) -> PlayerAction {
+    use PlayerAction::*;
+    use PlayerCommand::*;
+
+    // dead players don't get to do anything, and nor do ones who've already won
+    if !world.is_alive(game.player) || game.won {
+        return DidntTakeTurn;
+    }
+
+    // and paralysed ones lose their turn whatever they were trying to do
+    if world.has_effect(game.player, Effect::Paralysed) && !matches!(command, LevelUp(_)) {
+        game.messages.add("You can't move!", LIGHT_RED);
+        return TookTurn;
+    }
+
+    match command {
+        Move(dx, dy) => {
+            // confused players stumble off in any old direction
+            let (dx, dy) = if world.has_effect(game.player, Effect::Confused) {
+                (game.rng.gen_range(-1..2), game.rng.gen_range(-1..2))
+            } else {
+                (dx, dy)
+            };
+            player_move_or_attack(dx, dy, game, world);
+            TookTurn
+        },
+        Wait => TookTurn,
+        PickUp => {
+            let pos = world.positions[game.player];
+            let item_id = world.at(pos.x, pos.y).find(|&id| world.is_carryable(id));
+            if let Some(item_id) = item_id {
+                pick_item_up(item_id, game, world);
+            };
+            DidntTakeTurn
+        },
+        UseItem { inv_id, target } => {
+            if inv_id >= game.inventory.len() {
+                return DidntTakeTurn;
+            }
+            use_item(inv_id, target, game, world)
+        },
+        DropItem(inv_id) => {
+            if inv_id < game.inventory.len() {
+                drop_item(inv_id, game, world);
+            }
+            DidntTakeTurn
+        },
+        Equip(inv_id) => match game.inventory.get(inv_id) {
+            Some(&item_id) if world.equipment.get(item_id).is_some_and(|eq| eq.equipped_by.is_none()) => {
+                equip(item_id, game, world);
+                TookTurn
+            },
+            Some(&item_id) => {
+                game.messages.add(format!("The {} can't be equipped", world.name(item_id)), WHITE);
+                DidntTakeTurn
+            },
+            None => DidntTakeTurn,
+        },
+        Unequip(inv_id) => match game.inventory.get(inv_id) {
+            Some(&item_id) if world.equipment.get(item_id).is_some_and(|eq| eq.equipped_by.is_some()) => {
+                unequip(item_id, game, world);
+                TookTurn
+            },
+            _ => DidntTakeTurn,
+        },
+        Descend => {
+            if player_on(STAIRS_DOWN, game, world) {
+                next_level(game, world);
+            }
+            DidntTakeTurn
+        },
+        Ascend => {
+            if player_on(STAIRS_UP, game, world) {
+                previous_level(game, world);
+            }
+            DidntTakeTurn
+        },
+        LevelUp(stat) => {
+            if level_up_due(game, world) {
+                level_up(stat, game, world);
+            }
+            DidntTakeTurn
+        },
+        Explore => explore_step(game, world),
+        TravelToStairs => travel_to_stairs_step(game, world),
+    }
+}
+
+// Head for whichever bit of the level the player hasn't seen yet is closest
+fn explore_step(game: &mut Game, world: &mut World) -> PlayerAction {
+    if !fit_to_travel(game, world) {
+        return PlayerAction::DidntTakeTurn;
+    }
+
+    let mut unexplored = vec![];
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            let tile = game.map[x as usize][y as usize];
+            if !tile.explored && !tile.blocked {
+                unexplored.push((x, y));
+            }
+        }
+    }
+
+    let traps = known_traps(world);
+    let dijkstra = DijkstraMap::new(&game.map, &unexplored, |x, y| (!traps.contains(&(x, y))).then_some(1));
+    if step_downhill(&dijkstra, game, world) {
+        PlayerAction::TookTurn
+    } else {
+        game.messages.add("There's nowhere left to explore", WHITE);
+        PlayerAction::DidntTakeTurn
+    }
+}
+
+// Walk to the stairs, but only over ground the player has already seen
+fn travel_to_stairs_step(game: &mut Game, world: &mut World) -> PlayerAction {
+    if player_on(STAIRS_DOWN, game, world) || !fit_to_travel(game, world) {
+        return PlayerAction::DidntTakeTurn;
+    }
+
+    let stairs = world.positions.iter()
+        .find(|&(id, _)| world.name(id) == STAIRS_DOWN)
+        .map(|(_, pos)| (pos.x, pos.y))
+        .filter(|&(x, y)| game.map[x as usize][y as usize].explored);
+    let Some(stairs) = stairs else {
+        game.messages.add("You haven't found the stairs yet", WHITE);
+        return PlayerAction::DidntTakeTurn;
+    };
+
+    let map = &game.map;
+    let traps = known_traps(world);
+    let dijkstra = DijkstraMap::new(map, &[stairs], |x, y|
+        (map[x as usize][y as usize].explored && !traps.contains(&(x, y))).then_some(1)
+    );
+    if step_downhill(&dijkstra, game, world) {
+        PlayerAction::TookTurn
+    } else {
+        game.messages.add("You can't see a way to the stairs", WHITE);
+        PlayerAction::DidntTakeTurn
+    }
+}
+
+// Exploring and travelling are out while there's anything to fight or the player can't trust their own feet or eyes
+fn fit_to_travel(game: &mut Game, world: &World) -> bool {
+    if enemies_in_view(game, world) {
+        game.messages.add("Not with enemies about!", WHITE);
+        false
+    } else if world.has_effect(game.player, Effect::Confused) || world.has_effect(game.player, Effect::Blinded) {
+        game.messages.add("You're in no state to go wandering off", WHITE);
+        false
+    } else {
+        true
+    }
+}
+
+// Traps the player has found, which exploring and travelling won't walk over
+fn known_traps(world: &World) -> HashSet<(i32, i32)> {
+    world.traps.ids().into_iter()
+        .filter(|&id| world.renderables.contains(id))
+        .filter_map(|id| world.pos(id))
+        .map(|pos| (pos.x, pos.y))
+        .collect()
+}
+
+// Move the player one tile downhill, returning whether there was anywhere to go. It's an ordinary step, so any
+// hidden traps on the way still go off
+fn step_downhill(dijkstra: &DijkstraMap, game: &mut Game, world: &mut World) -> bool {
+    let pos = world.positions[game.player];
+    match dijkstra.best_step(pos.x, pos.y, |x, y| !is_blocked(x, y, &game.map, world)) {
+        Some((x, y)) => {
+            player_move_or_attack(x - pos.x, y - pos.y, game, world);
+            true
+        },
+        None => false,
+    }
+}
+
+// Whether the player can see anything that would want to fight them
+pub fn enemies_in_view(game: &Game, world: &World) -> bool {
+    world.ais.iter().any(|(id, _)|
+        world.is_alive(id) && world.pos(id).is_some_and(|pos| game.fov.is_in_fov(pos.x, pos.y))
+    )
+}
+
+// Whether the player's standing on something called `name`, e.g. one of the staircases
+pub fn player_on(name: &str, game: &Game, world: &World) -> bool {
+    let pos = world.positions[game.player];
+    world.at(pos.x, pos.y).any(|id| world.name(id) == name)
+}
+
+// map creation functions
+
+// Keep trying until a level comes out playable. The problems with any rejected attempts come back either way, and if
+// nothing works out after MAX_LEVEL_TRIES it's up to the caller what to do about it.
+fn make_map(rng: &mut GameRng, data: &GameData, depth: i32, player: EntityId, world: &mut World) -> Result<(Map, Vec<String>), Vec<String>> {
+    // anything from a failed attempt gets cleared out before the next one
+    let before: HashSet<_> = world.ids().into_iter().collect();
+    let mut rejected = vec![];
+    for _ in 0..MAX_LEVEL_TRIES {
+        match try_make_map(rng, data, depth, player, world) {
+            Ok(map) => return Ok((map, rejected)),
+            Err(problems) => {
+                rejected.extend(problems);
+                world.retain(|id| before.contains(&id));
+            },
+        }
+    }
+    rejected.push(format!("gave up after {} tries", MAX_LEVEL_TRIES));
+    Err(rejected)
+}
+
+fn try_make_map(rng: &mut GameRng, data: &GameData, depth: i32, player: EntityId, world: &mut World) -> Result<Map, Vec<String>> {
+    let choices: Vec<_> = data.dungeon.generators.iter().collect();
+    let generator = pick_weighted(rng, &choices, |choice| choice.weight.at(depth)).map_or(Generator::Rooms, |choice| choice.generator);
+    let Some(Layout { mut map, rooms, start, stairs }) = generate(generator, rng) else {
+        return Err(vec![format!("the {:?} generator couldn't make anything", generator)]);
+    };
+
+    // let's put @ here! :)
+    world.positions.insert(player, Position::new(start.0, start.1));
+
+    // the bottom of the dungeon has the boss and the artefact where the stairs down would be. They go in before
+    // anything else so nothing else can take the boss's spot
+    let final_level = depth >= data.dungeon.final_depth;
+    if final_level {
+        if let Some(template) = data.monsters.iter().find(|monster| monster.name == data.dungeon.boss) {
+            spawn_monster(template, stairs.0, stairs.1, world);
+        }
+        if let Some(template) = data.items.iter().find(|item| item.name == data.dungeon.artefact) {
+            spawn_item(template, stairs.0, stairs.1, world);
+        }
+    }
+
+    for &room in &rooms {
+        place_objects(room, &map, rng, data, depth, world);
+    }
+
+    let vaults: Vec<_> = data.vaults.iter().collect();
+    let mut placed = vec![];
+    let num_vaults = rng.gen_range(0..data.dungeon.max_vaults.at(depth) as i32 + 1);
+    for _ in 0..num_vaults {
+        if let Some(vault) = pick_weighted(rng, &vaults, |vault| vault.spawn_weight.at(depth)) {
+            if let Some((x, y)) = place_vault(vault, &mut map, &mut placed, rng) {
+                fill_vault(vault, x, y, data, world);
+            }
+        }
+    }
+
+    // the player and the stairs get checked as the start and the way down
+    let spawns: Vec<_> = world.positions.iter().filter(|&(id, _)| id != player)
+        .map(|(id, pos)| (world.name(id).to_string(), (pos.x, pos.y))).collect();
+    let problems = check_level(&map, start, stairs, &spawns);
+
+    if !final_level {
+        let stairs = world.spawn_at(STAIRS_DOWN, stairs.0, stairs.1, '>', WHITE, false);
+        world.renderables[stairs].always_visible = true;
+    }
+
+    if problems.is_empty() {
+        Ok(map)
+    } else {
+        Err(problems.into_iter().map(|problem| format!("{} level: {}", format!("{:?}", generator).to_lowercase(), problem)).collect())
+    }
+}
+
+// Make `levels` levels from the seed, one after another like going down the stairs, and report everything the checks
+// turned up - including attempts that were thrown away and tried again. There's no playing involved so the levels
+// after the first won't match a real game from the same seed.
+pub fn check_levels(seed: u64, levels: i32, data: &GameData) -> Vec<String> {
+    let mut rng = GameRng::seed_from_u64(seed);
+    let mut world = World::new();
+    let player = world.spawn("Player");
+    let mut problems = vec![];
+    for depth in 1..levels + 1 {
+        world.retain(|id| id == player);
+        let (rejected, gave_up) = match make_map(&mut rng, data, depth, player, &mut world) {
+            Ok((_, rejected)) => (rejected, false),
+            Err(rejected) => (rejected, true),
+        };
+        problems.extend(rejected.into_iter().map(|problem| format!("seed {} depth {}: {}", seed, depth, problem)));
+        // there's no getting any deeper on this seed
+        if gave_up {
+            break;
+        }
+    }
+    problems
+}
+
+fn place_objects(room: Rect, map: &Map, rng: &mut GameRng, data: &GameData, depth: i32, world: &mut World) {
+    // only the monsters that belong at this depth get a look in
+    let monsters: Vec<_> = data.monsters.iter().filter(|monster| monster.spawns_at(depth)).collect();
+
+    let max_monsters = data.dungeon.max_room_monsters.at(depth) as i32;
+    let num_monsters = rng.gen_range(0..max_monsters + 1);
+    for _ in 0..num_monsters {
+        let x = rng.gen_range(room.x1+1..room.x2);
+        let y = rng.gen_range(room.y1+1..room.y2);
+
+        if !is_blocked(x, y, map, world) {
+            if let Some(template) = pick_weighted(rng, &monsters, |monster| monster.spawn_weight.at(depth)) {
+                spawn_monster(template, x, y, world);
+            }
+        }
+    }
+
+    let items: Vec<_> = data.items.iter().collect();
+
+    let max_items = data.dungeon.max_room_items.at(depth) as i32;
+    let num_items = rng.gen_range(0..max_items + 1);
+    for _ in 0..num_items {
+        let x = rng.gen_range(room.x1+1..room.x2);
+        let y = rng.gen_range(room.y1+1..room.y2);
+
+        if !is_blocked(x, y, map, world) {
+            if let Some(template) = pick_weighted(rng, &items, |item| item.spawn_weight.at(depth)) {
+                spawn_item(template, x, y, world);
+            }
+        }
+    }
+
+    let traps: Vec<_> = data.traps.iter().collect();
+
+    let max_traps = data.dungeon.max_room_traps.at(depth) as i32;
+    let num_traps = rng.gen_range(0..max_traps + 1);
+    for _ in 0..num_traps {
+        let x = rng.gen_range(room.x1+1..room.x2);
+        let y = rng.gen_range(room.y1+1..room.y2);
+
+        // one thing per tile, so a trap never hides under a monster or an item
+        if !is_blocked(x, y, map, world) && world.at(x, y).next().is_none() {
+            if let Some(template) = pick_weighted(rng, &traps, |trap| trap.spawn_weight.at(depth)) {
+                spawn_trap(template, x, y, world);
+            }
+        }
+    }
+}
+
+fn spawn_monster(template: &MonsterTemplate, x: i32, y: i32, world: &mut World) -> EntityId {
+    let monster = world.spawn_at(&template.name, x, y, template.glyph, template.colour, true);
+    world.fighters.insert(monster, Fighter {
+        max_hp: template.hp,
+        hp: template.hp,
+        defence: template.defence,
+        power: template.power,
+        xp: template.xp,
+        level: 1,
+        on_death: DeathCallback::Monster,
+    });
+    world.ais.insert(monster, template.ai);
+    world.actors.insert(monster, Actor::new(template.speed));
+    if let Some(on_hit) = template.on_hit {
+        world.inflicts.insert(monster, on_hit);
+    }
+    monster
+}
+
+// Put whatever the vault's legend says on each of its tiles. The names have all been checked when the data was loaded
+fn fill_vault(vault: &VaultTemplate, x: i32, y: i32, data: &GameData, world: &mut World) {
+    for (vx, vy, chr) in vault.cells() {
+        let Some(cell) = vault.legend.get(&chr) else {
+            continue;
+        };
+        let (cx, cy) = (x + vx, y + vy);
+        if let Some(template) = cell.monster.as_ref().and_then(|name| data.monsters.iter().find(|m| &m.name == name)) {
+            spawn_monster(template, cx, cy, world);
+        }
+        if let Some(template) = cell.item.as_ref().and_then(|name| data.items.iter().find(|i| &i.name == name)) {
+            spawn_item(template, cx, cy, world);
+        }
+        if let Some(template) = cell.trap.as_ref().and_then(|name| data.traps.iter().find(|t| &t.name == name)) {
+            spawn_trap(template, cx, cy, world);
+        }
+    }
+}
+
+// Traps start out hidden - they only get something to draw once they've been set off
+fn spawn_trap(template: &TrapTemplate, x: i32, y: i32, world: &mut World) -> EntityId {
+    let trap = world.spawn(&template.name);
+    world.positions.insert(trap, Position::new(x, y));
+    world.traps.insert(trap, Trap {
+        revealed: Renderable { chr: template.glyph, colour: template.colour, always_visible: true },
+    });
+    world.inflicts.insert(trap, template.inflicts);
+    trap
+}
+
+fn spawn_item(template: &ItemTemplate, x: i32, y: i32, world: &mut World) -> EntityId {
+    let item = world.spawn_at(&template.name, x, y, template.glyph, template.colour, false);
+    if let Some(effect) = template.effect {
+        world.items.insert(item, effect);
+    }
+    if let Some(equipment) = &template.equipment {
+        world.equipment.insert(item, Equipment {
+            slot: equipment.slot,
+            power_bonus: equipment.power,
+            defence_bonus: equipment.defence,
+            max_hp_bonus: equipment.max_hp,
+            equipped_by: None,
+        });
+    }
+    item
+}
+
+fn is_blocked(x: i32, y: i32, map: &Map, world: &World) -> bool {
+    if map[x as usize][y as usize].blocked {
+        return true;
+    }
+
+    world.at(x, y).any(|id| world.blocks.contains(id))
+}
+
+// Tick the clock until the player has the energy to act again. Every tick everything gains its speed in energy and
+// anything with an AI that has enough gets its turn.
+fn pass_time(game: &mut Game, world: &mut World) {
+    // (a player with nothing to act with would never be ready, so there's no waiting for them)
+    while world.is_alive(game.player) && world.actors.contains(game.player) && !ready_to_act(game.player, world) {
+        // only things on the player's level have a position, everywhere else waits for the player to come back
+        for id in world.actors.ids() {
+            if world.positions.contains(id) {
+                world.actors[id].energy += world.speed(id);
+            }
+        }
+
+        for id in world.ais.ids() {
+            // anything that lost its AI earlier in the tick (e.g. it died) just gets skipped
+            if world.ais.contains(id) && world.positions.contains(id) && ready_to_act(id, world) {
+                ai_take_turn(id, game, world);
+                spend_energy(id, world);
+                tick_effects(id, game, world);
+            }
+        }
+    }
+}
+
+fn ready_to_act(id: EntityId, world: &World) -> bool {
+    world.actors.get(id).is_some_and(|actor| actor.energy >= ACTION_COST)
+}
+
+fn spend_energy(id: EntityId, world: &mut World) {
+    if let Some(actor) = world.actors.get_mut(id) {
+        actor.energy -= ACTION_COST;
+    }
+}
+
+fn ai_take_turn(id: EntityId, game: &mut Game, world: &mut World) {
+    use AI::*;
+    // whatever a monster would normally do, its status effects come first
+    if world.has_effect(id, Effect::Paralysed) {
+        return;
+    }
+    if world.has_effect(id, Effect::Confused) {
+        move_confused(id, game, world);
+        return;
+    }
+
+    if let Some(ai) = world.ais.remove(id) {
+        let new_ai = match ai {
+            Basic => ai_basic(id, game, world),
+            Cowardly => ai_cowardly(id, game, world),
+        };
+
+        world.ais.insert(id, new_ai);
+    }
+}
+
+// Monsters can only go after the player while they can see them
+fn can_see_player(monster_id: EntityId, game: &Game, world: &World) -> bool {
+    let pos = world.positions[monster_id];
+    game.sight.is_in_fov(pos.x, pos.y) && !world.has_effect(monster_id, Effect::Blinded)
+}
+
+fn ai_basic(monster_id: EntityId, game: &mut Game, world: &mut World) -> AI {
+    let player_id = game.player;
+    let monster_pos = world.positions[monster_id];
+    if can_see_player(monster_id, game, world) {
+        let player_pos = world.positions[player_id];
+        if monster_pos.distance_to(player_pos) >= 2.0 {
+            // Move towards player
+            move_along_path(monster_id, player_pos.x, player_pos.y, &game.map, world);
+        } else if world.is_alive(player_id) {
+            // ATTTACK!!!!!
+            attack(monster_id, player_id, game, world);
+        }
+    }
+    AI::Basic
+}
+
+fn ai_cowardly(monster_id: EntityId, game: &mut Game, world: &mut World) -> AI {
+    let hp = world.fighters[monster_id].hp;
+    let badly_hurt = (hp as f32) < world.max_hp(monster_id) as f32 * FLEE_HP_FRACTION;
+    let in_view = can_see_player(monster_id, game, world);
+    // cornered, it fights like anything else would
+    if !(badly_hurt && in_view && flee_from(monster_id, game.player, &game.map, world)) {
+        ai_basic(monster_id, game, world);
+    }
+    AI::Cowardly
+}
+
+fn move_confused(id: EntityId, game: &mut Game, world: &mut World) {
+    let (dx, dy) = (game.rng.gen_range(-1..2), game.rng.gen_range(-1..2));
+    move_by(id, dx, dy, &game.map, world);
+}
+
+// Take the first step of the best path to the target. If there isn't one, or something has stepped into the way,
+// just head straight for it instead
+fn move_along_path(id: EntityId, target_x: i32, target_y: i32, map: &Map, world: &mut World) {
+    let pos = world.positions[id];
+    let in_the_way: HashSet<_> = world.blocks.iter()
+        .filter_map(|(other, _)| world.pos(other))
+        .map(|other| (other.x, other.y))
+        .collect();
+    let path = find_path(map, (pos.x, pos.y), (target_x, target_y), |x, y|
+        if in_the_way.contains(&(x, y)) { PATH_BLOCKED_COST } else { 0 }
+    );
+
+    match path.and_then(|path| path.first().copied()) {
+        Some((x, y)) if !is_blocked(x, y, map, world) => world.positions[id] = Position::new(x, y),
+        _ => move_towards(id, target_x, target_y, map, world),
+    }
+}
+
+// Take a step that gets further away from the other entity, heading for open ground rather than a corner. Returns
+// whether there was anywhere to go.
+fn flee_from(id: EntityId, from: EntityId, map: &Map, world: &mut World) -> bool {
+    let (pos, from_pos) = (world.positions[id], world.positions[from]);
+    let towards = DijkstraMap::new(map, &[(from_pos.x, from_pos.y)], |_, _| Some(1));
+    let away = towards.flee(map, |_, _| Some(1));
+    match away.best_step(pos.x, pos.y, |x, y| !is_blocked(x, y, map, world)) {
+        Some((x, y)) => {
+            world.positions[id] = Position::new(x, y);
+            true
+        },
+        None => false,
+    }
+}
+
+fn move_towards(id: EntityId, target_x: i32, target_y: i32, map: &Map, world: &mut World) {
+    // figure direction vector out
+    let pos = world.positions[id];
+    let dx = target_x - pos.x;
+    let dy = target_y - pos.y;
+    let distance = ((dx*dx + dy*dy) as f32).sqrt();
+
+    // normalise vector to unit - mmm type conversions
+    let dx = (dx as f32 / distance).round() as i32;
+    let dy = (dy as f32 / distance).round() as i32;
+    move_by(id, dx, dy, map, world);
+}
+
+// Move this entity by the given delta
+fn move_by(id: EntityId, dx: i32, dy: i32, map: &Map, world: &mut World) {
+    let pos = world.positions[id];
+    if !is_blocked(pos.x + dx, pos.y + dy, map, world) {
+        world.positions[id] = Position::new(pos.x + dx, pos.y + dy);
+    }
+}
+
+fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, world: &mut World) {
+    let player_id = game.player;
+    let pos = world.positions[player_id];
+    let (x, y) = (pos.x + dx, pos.y + dy);
+    let target_id = world.at(x, y).find(|&id| id != player_id && world.fighters.contains(id));
+
+    match target_id {
+        Some(target_id) => {
+            // Attackable target
+            attack(player_id, target_id, game, world);
+        },
+        None => {
+            // Player move
+            move_by(player_id, dx, dy, &game.map, world);
+            if world.positions[player_id] != pos {
+                spring_traps(game, world);
+            }
+        }
+    }
+}
+
+// Set off anything nasty where the player has just stepped. Found traps stay on the map from then on
+fn spring_traps(game: &mut Game, world: &mut World) {
+    let pos = world.positions[game.player];
+    let traps: Vec<_> = world.at(pos.x, pos.y).filter(|&id| world.traps.contains(id)).collect();
+    for id in traps {
+        game.messages.add(format!("You set off a {}!", world.name(id)), ORANGE);
+        game.disturbed = true;
+        let revealed = world.traps[id].revealed;
+        world.renderables.insert(id, revealed);
+        if let Some(&inflicts) = world.inflicts.get(id) {
+            inflict(game.player, inflicts, game, world);
+        }
+    }
+}
+
+// status effects
+
+// "The orc" or "You", for messages about either
+fn subject(id: EntityId, game: &Game, world: &World) -> String {
+    if id == game.player {
+        "You".into()
+    } else {
+        format!("The {}", world.name(id))
+    }
+}
+
+fn add_effect(id: EntityId, new: StatusEffect, game: &mut Game, world: &mut World) {
+    let subject = subject(id, game, world);
+    let verb = if id == game.player { "are" } else { "is" };
+    let mut effects = world.effects.remove(id).unwrap_or_default();
+
+    if let Some(opposite) = new.effect.opposite() {
+        if effects.iter().any(|e| e.effect == opposite) {
+            effects.retain(|e| e.effect != opposite);
+            game.messages.add(format!("{} {} no longer {}", subject, verb, opposite.describe()), LIGHT_GREEN);
+            world.effects.insert(id, effects);
+            return;
+        }
+    }
+
+    match effects.iter_mut().find(|e| e.effect == new.effect) {
+        Some(existing) => match new.effect.stacking() {
+            Stacking::Refresh => existing.turns = cmp::max(existing.turns, new.turns),
+            Stacking::Extend => {
+                existing.turns += new.turns;
+                existing.strength = cmp::max(existing.strength, new.strength);
+            },
+            Stacking::Intensify => {
+                existing.turns = cmp::max(existing.turns, new.turns);
+                existing.strength += new.strength;
+            },
+        },
+        None => effects.push(new),
+    }
+    game.messages.add(format!("{} {} {}!", subject, verb, new.effect.describe()), LIGHT_GREEN);
+    world.effects.insert(id, effects);
+}
+
+// Roll for whether the effect takes hold, and give it to the target if it does
+fn inflict(target: EntityId, inflicts: Inflicts, game: &mut Game, world: &mut World) {
+    if game.rng.gen_range(0..100) < inflicts.chance {
+        let status = StatusEffect { effect: inflicts.effect, turns: inflicts.turns, strength: inflicts.strength };
+        add_effect(target, status, game, world);
+    }
+}
+
+// Called at the end of each of the creature's turns - does whatever its effects do each turn and wears them down
+fn tick_effects(id: EntityId, game: &mut Game, world: &mut World) {
+    let Some(mut effects) = world.effects.remove(id) else {
+        return;
+    };
+
+    let subject = subject(id, game, world);
+    let verb = if id == game.player { "are" } else { "is" };
+    for status in effects.iter_mut() {
+        // poison may have killed it, and the dead don't carry on being poisoned
+        if !world.is_alive(id) {
+            return;
+        }
+        match status.effect {
+            Effect::Poisoned => {
+                game.messages.add(format!("{} {} hurt by the poison", subject, verb), LIGHT_RED);
+                take_damage(id, status.strength, "poison", game, world);
+            },
+            Effect::Regenerating => heal(id, status.strength, world),
+            _ => {},
+        }
+        status.turns -= 1;
+        if status.turns <= 0 {
+            game.messages.add(format!("{} {} no longer {}", subject, verb, status.effect.describe()), LIGHT_GREEN);
+        }
+    }
+
+    effects.retain(|status| status.turns > 0);
+    if !effects.is_empty() && world.is_alive(id) {
+        world.effects.insert(id, effects);
+    }
+}
+
+// fighter systems
+
+// `cause` is what gets the blame if this kills the player
+fn take_damage(id: EntityId, damage: i32, cause: &str, game: &mut Game, world: &mut World) -> Option<i32> {
+    let fighter = world.fighters.get_mut(id)?;
+    if damage > 0 {
+        fighter.hp -= damage;
+    }
+
+    // copy it out so the death callback is free to change the world
+    let fighter = *fighter;
+    if fighter.hp <= 0 {
+        if id == game.player {
+            game.stats.killed_by = Some(cause.to_string());
+        }
+        fighter.on_death.callback(id, game, world);
+        return Some(fighter.xp);
+    }
+
+    None
+}
+
+fn heal(id: EntityId, amount: i32, world: &mut World) {
+    let max_hp = world.max_hp(id);
+    if let Some(fighter) = world.fighters.get_mut(id) {
+        fighter.hp += amount;
+        if fighter.hp > max_hp {
+            fighter.hp = max_hp;
+        }
+    }
+}
+
+fn attack(attacker: EntityId, target: EntityId, game: &mut Game, world: &mut World) {
+    let damage = world.power(attacker) - world.defence(target);
+    let attacker_name = world.name(attacker).to_string();
+    let target_name = world.name(target).to_string();
+    if damage > 0 {
+        game.messages.add(format!("{} attacks {} for {} hp", attacker_name, target_name, damage), WHITE);
+        if let Some(xp) = take_damage(target, damage, &attacker_name, game, world) {
+            world.fighters[attacker].xp += xp;
+        } else if let Some(&on_hit) = world.inflicts.get(attacker) {
+            // anything nasty about the attack only matters if the target lived through it
+            inflict(target, on_hit, game, world);
+        }
+    } else {
+        game.messages.add(format!("{} attacks {} but it has no effect", attacker_name, target_name), WHITE);
+    }
+}
+
+fn next_level(game: &mut Game, world: &mut World) {
+    leave_level(game, world);
+    game.depth += 1;
+
+    if let Some(level) = game.levels.remove(&game.depth) {
+        game.messages.add("You head back down into the dungeon", RED);
+        return_to_level(level, STAIRS_UP, game, world);
+        return;
+    }
+
+    // generate next level, with a way back up where the player comes in
+    match make_map(&mut game.rng, &game.data, game.depth, game.player, world) {
+        Ok((map, _)) => game.map = map,
+        Err(_) => {
+            // stay put on the stairs rather than fall into a level that doesn't work
+            game.depth -= 1;
+            let level = game.levels.remove(&game.depth).expect("the level being left was just stored");
+            game.messages.add("The way down is blocked. Perhaps it'll clear up another time", LIGHT_RED);
+            return_to_level(level, STAIRS_DOWN, game, world);
+            return;
+        },
+    }
+    game.messages.add("You take the opportunity for a quick rest", VIOLET);
+    let heal_hp = world.max_hp(game.player) / 2;
+    heal(game.player, heal_hp, world);
+    game.messages.add("YOu take a deep breath before heading deeper into the dungeon", RED);
+    game.stats.deepest = cmp::max(game.stats.deepest, game.depth);
+    spawn_up_stairs(game, world);
+    if game.depth >= game.data.dungeon.final_depth {
+        game.messages.add(format!("You feel the presence of the {} somewhere on this level", game.data.dungeon.boss), LIGHT_RED);
+    }
+    update_fov(game, world);
+}
+
+// The way back up goes wherever the player arrives on a new level. On the first level it's the way out
+fn spawn_up_stairs(game: &Game, world: &mut World) {
+    let pos = world.positions[game.player];
+    let stairs = world.spawn_at(STAIRS_UP, pos.x, pos.y, '<', WHITE, false);
+    world.renderables[stairs].always_visible = true;
+}
+
+// The level above has always been visited already, since the only way down is through it
+fn previous_level(game: &mut Game, world: &mut World) {
+    if game.depth == 1 {
+        leave_dungeon(game, world);
+        return;
+    }
+    let Some(level) = game.levels.remove(&(game.depth - 1)) else {
+        game.messages.add("The way up has caved in", RED);
+        return;
+    };
+    leave_level(game, world);
+    game.depth -= 1;
+    game.messages.add("You climb back up the stairs", RED);
+    return_to_level(level, STAIRS_DOWN, game, world);
+}
+
+// Climbing out of the top of the dungeon wins the game, as long as the player's got the artefact with them
+fn leave_dungeon(game: &mut Game, world: &World) {
+    let artefact = game.data.dungeon.artefact.clone();
+    if game.inventory.iter().any(|&id| world.name(id) == artefact) {
+        game.messages.add(format!("You climb out of the dungeon with the {}!", artefact), LIGHT_YELLOW);
+        game.won = true;
+    } else {
+        game.messages.add(format!("You can't leave without the {}", artefact), WHITE);
+    }
+}
+
+// Pack away the current level - its map and where everything on it is - ready for the player to come back to
+fn leave_level(game: &mut Game, world: &mut World) {
+    let positions: Vec<_> = world.positions.iter()
+        .filter(|&(id, _)| id != game.player)
+        .map(|(id, &pos)| (id, pos))
+        .collect();
+    for &(id, _) in &positions {
+        world.positions.remove(id);
+    }
+    let map = std::mem::take(&mut game.map);
+    game.levels.insert(game.depth, Level { map, positions });
+}
+
+// Unpack a level the player has been to before, putting them on the staircase they'd have come in by
+fn return_to_level(level: Level, arrive_on: &str, game: &mut Game, world: &mut World) {
+    game.map = level.map;
+    for (id, pos) in level.positions {
+        world.positions.insert(id, pos);
+    }
+    let arrival = world.positions.iter().find(|&(id, _)| world.name(id) == arrive_on).map(|(_, &pos)| pos);
+    if let Some(pos) = arrival {
+        world.positions.insert(game.player, pos);
+    }
+    update_fov(game, world);
+}
+
+fn pick_item_up(item_id: EntityId, game: &mut Game, world: &mut World) {
+    if game.inventory.len() >= MAX_INVENTORY {
+        game.messages.add(format!("Your inventory is full. Cannot pick up {}", world.name(item_id)), RED);
+    } else {
+        world.positions.remove(item_id);
+        game.messages.add(format!("You have picked up a {}", world.name(item_id)), GREEN);
+        game.inventory.push(item_id);
+    }
+}
+
+fn drop_item(inv_id: usize, game: &mut Game, world: &mut World) {
+    let item_id = game.inventory[inv_id];
+    if world.equipment.get(item_id).is_some_and(|eq| eq.equipped_by.is_some()) {
+        unequip(item_id, game, world);
+    }
+    game.inventory.remove(inv_id);
+    let pos = world.positions[game.player];
+    world.positions.insert(item_id, pos);
+    game.messages.add(format!("You dropped a {}", world.name(item_id)), YELLOW);
+}
+
+fn equip(item_id: EntityId, game: &mut Game, world: &mut World) {
+    let owner = game.player;
+    let slot = world.equipment[item_id].slot;
+
+    // take something off if the slot's already full
+    let worn: Vec<_> = world.equipped(owner).filter(|(_, eq)| eq.slot == slot).map(|(id, _)| id).collect();
+    if worn.len() >= slot.capacity() {
+        unequip(worn[0], game, world);
+    }
+
+    world.equipment[item_id].equipped_by = Some(owner);
+    game.messages.add(format!("You equip the {} as your {}", world.name(item_id), slot), LIGHT_GREEN);
+}
+
+fn unequip(item_id: EntityId, game: &mut Game, world: &mut World) {
+    let owner = world.equipment[item_id].equipped_by.take();
+    game.messages.add(format!("You take off the {}", world.name(item_id)), LIGHT_YELLOW);
+
+    // losing max hp can't leave the wearer with more hp than they can now have
+    if let Some(owner) = owner {
+        let max_hp = world.max_hp(owner);
+        if let Some(fighter) = world.fighters.get_mut(owner) {
+            fighter.hp = cmp::min(fighter.hp, max_hp);
+        }
+    }
+}
+
+// Tell the front end whether it needs to pick a target before using this inventory item
+pub fn item_targeting(inv_id: usize, game: &Game, world: &World) -> Targeting {
+    use Item::*;
+    match game.inventory.get(inv_id).and_then(|&id| world.items.get(id)) {
+        Some(Fireball { .. }) => Targeting::Tile { max_range: None },
+        Some(&Confuse { range, .. }) => Targeting::Monster { max_range: Some(range as f32) },
+        Some(Heal { .. }) | Some(Lightning { .. }) | Some(Status { .. }) | None => Targeting::None,
+    }
+}
+
+fn use_item(inv_id: usize, target: Option<(i32, i32)>, game: &mut Game, world: &mut World) -> PlayerAction {
+    use Item::*;
+    let item_id = game.inventory[inv_id];
+    if let Some(&item) = world.items.get(item_id) {
+        let result = match item {
+            Heal { amount } => cast_heal(amount, game, world),
+            Lightning { damage, range } => cast_lightning(damage, range, game, world),
+            Fireball { damage, radius } => cast_fireball(damage, radius, target, game, world),
+            Confuse { range, turns } => cast_confuse(range, turns, target, game, world),
+            Status { effect, turns, strength } => {
+                add_effect(game.player, StatusEffect { effect, turns, strength }, game, world);
+                UseResult::UsedUp
+            },
+        };
+
+        match result {
+            UseResult::UsedUp => {
+                game.inventory.remove(inv_id);
+                world.despawn(item_id);
+                PlayerAction::TookTurn
+            },
+            UseResult::Cancelled => {
+                game.messages.add("Cancelled", WHITE);
+                PlayerAction::DidntTakeTurn
+            }
+        }
+    } else if let Some(equipment) = world.equipment.get(item_id) {
+        // using a piece of equipment puts it on or takes it off
+        if equipment.equipped_by.is_some() {
+            unequip(item_id, game, world);
+        } else {
+            equip(item_id, game, world);
+        }
+        PlayerAction::TookTurn
+    } else {
+        game.messages.add(format!("The {} cannot be used", world.name(item_id)), WHITE);
+        PlayerAction::DidntTakeTurn
+    }
+}
+
+fn cast_heal(amount: i32, game: &mut Game, world: &mut World) -> UseResult {
+    if let Some(&fighter) = world.fighters.get(game.player) {
+        if fighter.hp == world.max_hp(game.player) {
+            game.messages.add("Already at full health", ORANGE);
+            return UseResult::Cancelled;
+        } else {
+            game.messages.add("Your wounds start to feel better", LIGHT_VIOLET);
+            heal(game.player, amount, world);
+            return UseResult::UsedUp;
+        }
+    }
+    UseResult::Cancelled
+}
+
+fn cast_lightning(damage: i32, range: i32, game: &mut Game, world: &mut World) -> UseResult {
+    let monster_id = closest_monster(game, world, range);
+    if let Some(monster_id) = monster_id {
+        game.messages.add(format!("A lightning bolt strikes the {} with a loud clap. It did {} points oif damage", world.name(monster_id), damage), LIGHT_BLUE);
+        if let Some(xp) = take_damage(monster_id, damage, "lightning", game, world) {
+            world.fighters[game.player].xp += xp;
+        }
+        UseResult::UsedUp
+    } else {
+        game.messages.add("There are no targets close enough", RED);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_confuse(range: i32, turns: i32, target: Option<(i32, i32)>, game: &mut Game, world: &mut World) -> UseResult {
+    // the front end has already done the clicking so all we get is the tile the player picked
+    let player_pos = world.positions[game.player];
+    let monster_id = target
+        .filter(|&(x, y)| player_pos.distance(x, y) <= range as f32)
+        .and_then(|(x, y)| monster_at(x, y, game, world));
+    if let Some(monster_id) = monster_id {
+        game.messages.add(format!("The eyes of the {} glaze over", world.name(monster_id)), LIGHT_GREEN);
+        add_effect(monster_id, StatusEffect { effect: Effect::Confused, turns, strength: 0 }, game, world);
+        UseResult::UsedUp
+    } else {
+        game.messages.add("No enemy close enough to confuse", RED);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_fireball(damage: i32, radius: i32, target: Option<(i32, i32)>, game: &mut Game, world: &mut World) -> UseResult {
+    let (x, y) = match target {
+        Some(tile_pos) => tile_pos,
+        None => return UseResult::Cancelled
+    };
+
+    let player_id = game.player;
+    let burned: Vec<_> = world.fighters.ids().into_iter()
+        .filter(|&id| world.pos(id).is_some_and(|pos| pos.distance(x, y) <= radius as f32))
+        .collect();
+
+    let mut xpgain = 0;
+    game.messages.add(format!("The fireball explodes burning everything within {} tiles!", radius), ORANGE);
+    for id in burned {
+        game.messages.add(format!("The {} gets burned for {} damage", world.name(id), damage), ORANGE);
+        if let Some(xp) = take_damage(id, damage, "your own fireball", game, world) {
+            if id != player_id {
+                xpgain += xp;
+            }
+        }
+    }
+
+    world.fighters[player_id].xp += xpgain;
+
+    UseResult::UsedUp
+}
+
+pub fn level_up_due(game: &Game, world: &World) -> bool {
+    world.fighters.get(game.player).is_some_and(|pl| pl.xp >= LEVEL_UP_BASE + pl.level * LEVEL_UP_FACTOR)
+}
+
+fn level_up(stat: Stat, game: &mut Game, world: &mut World) {
+    let fighter = &mut world.fighters[game.player];
+    fighter.level += 1;
+    game.messages.add(format!("You are getting stronger, more skilled. Welcome to level {}", fighter.level), YELLOW);
+
+    match stat {
+        Stat::Constitution => {
+            fighter.max_hp += 20;
+            fighter.hp += 20;
+        },
+        Stat::Strength => {
+            fighter.power += 1;
+        },
+        Stat::Agility => {
+            fighter.defence += 1;
+        },
+    }
+}
+
+fn player_death(player: EntityId, game: &mut Game, world: &mut World) {
+    game.messages.add("You died!", RED);
+    if let Some(renderable) = world.renderables.get_mut(player) {
+        renderable.chr = '%';
+        renderable.colour = DARK_RED;
+    }
+}
+
+fn monster_death(monster: EntityId, game: &mut Game, world: &mut World) {
+    let name = world.name(monster).to_string();
+    game.messages.add(format!("The {} is dead! You gain {} XP", name, world.fighters[monster].xp), ORANGE);
+    if let Some(renderable) = world.renderables.get_mut(monster) {
+        renderable.chr = '%';
+        renderable.colour = DARK_RED;
+    }
+    world.blocks.remove(monster);
+    world.fighters.remove(monster);
+    world.ais.remove(monster);
+    world.actors.remove(monster);
+    world.inflicts.remove(monster);
+    world.names.insert(monster, format!("remains of {}", name));
+    *game.stats.kills.entry(name).or_insert(0) += 1;
+}
+
+fn closest_monster(game: &Game, world: &World, max_range: i32) -> Option<EntityId> {
+    let mut closest_enemy = None;
+    let mut closest_dist = (max_range + 1) as f32;
+    let player_pos = world.positions[game.player];
+
+    for (id, _) in world.ais.iter() {
+        if let Some(pos) = world.pos(id) {
+            if id != game.player && world.fighters.contains(id) && game.fov.is_in_fov(pos.x, pos.y) {
+                let dist = player_pos.distance_to(pos);
+                if dist < closest_dist {
+                    closest_enemy = Some(id);
+                    closest_dist = dist;
+                }
+            }
+        }
+    }
+
+    closest_enemy
+}
+
+// Find something other than the player that can be fought on the given tile
+pub fn monster_at(x: i32, y: i32, game: &Game, world: &World) -> Option<EntityId> {
+    world.at(x, y).find(|&id| id != game.player && world.fighters.contains(id))
+}
+
+// Pick one of the options at random, with the odds of each set by its weight
+fn pick_weighted<'a, T>(rng: &mut GameRng, options: &[&'a T], weight: impl Fn(&T) -> u32) -> Option<&'a T> {
+    let total: u32 = options.iter().map(|&option| weight(option)).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut roll = rng.gen_range(0..total);
+    for &option in options {
+        if roll < weight(option) {
+            return Some(option);
+        }
+        roll -= weight(option);
+    }
+    None
+}
+
+// Fails if the first level can't be made, which means the data needs looking at (try --check-levels)
+pub fn new_game(seed: u64, data: Rc<GameData>) -> Result<(Game, World), Box<dyn Error>> {
+    // Game objects
+    let mut world = World::new();
+    let player = world.spawn_at("Player", 0, 0, '@', WHITE, true);
+    world.fighters.insert(player, Fighter { max_hp: 30, hp: 30, defence: 2, power: 5, xp: 0, level: 1, on_death: DeathCallback::Player });
+    // the player starts out ready to go
+    world.actors.insert(player, Actor { speed: NORMAL_SPEED, energy: ACTION_COST });
+
+    let mut game = Game {
+        map: vec![], messages: Messages::new(), inventory: vec![], player, depth: 1, levels: BTreeMap::new(),
+        stats: Stats { deepest: 1, ..Default::default() }, won: false, seed, rng: GameRng::seed_from_u64(seed),
+        commands: vec![], data, fov: new_fov_map(), disturbed: false, sight: new_fov_map(),
+    };
+    game.map = match make_map(&mut game.rng, &game.data, game.depth, player, &mut world) {
+        Ok((map, _)) => map,
+        Err(problems) => return Err(format!("Couldn't make the first level for seed {}:\n  {}", seed, problems.join("\n  ")).into()),
+    };
+    spawn_up_stairs(&game, &mut world);
+
+    update_fov(&mut game, &world);
+    game.messages.add("Welcome stranger! Something something foreboding something something death", RED);
+
+    Ok((game, world))
+}
+
+// Work out what the player can see from where they're standing and remember any tiles they've now seen
+pub fn update_fov(game: &mut Game, world: &World) {
+    let player = world.positions[game.player];
+    game.sight.compute(&game.map, player.x, player.y, TORCH_RADIUS);
+    // blind players can only make out what's right next to them
+    if world.has_effect(game.player, Effect::Blinded) {
+        game.fov.compute(&game.map, player.x, player.y, 1);
+    } else {
+        game.fov.clone_from(&game.sight);
+    }
+
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            if game.fov.is_in_fov(x, y) {
+                game.map[x as usize][y as usize].explored = true;
+            }
+        }
+    }
+}
+
+pub fn save_game(game: &Game, world: &World) -> Result<(), Box<dyn Error>>{
+    let save_data = serde_json::to_string(&(game, world))?;
+    let mut file = File::create(SAVE_FILE)?;
+    file.write_all(save_data.as_bytes())?;
+    Ok(())
+}
+
+// Once a run's over, win or lose, there's nothing to go back to. Not having a save to delete is fine too
+pub fn delete_save() -> Result<(), Box<dyn Error>> {
+    match std::fs::remove_file(SAVE_FILE) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+pub fn load_game(data: Rc<GameData>) -> Result<(Game, World), Box<dyn Error>> {
+    let mut saved_state = String::new();
+    let mut file = File::open(SAVE_FILE)?;
+    file.read_to_string(&mut saved_state)?;
+    let (mut game, world) = serde_json::from_str::<(Game, World)>(&saved_state)?;
+    game.data = data;
+    update_fov(&mut game, &world);
+    Ok((game, world))
+}
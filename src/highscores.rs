@@ -0,0 +1,159 @@
+// The high score table. It lives in its own file next to the save game so it's still there after the runs on it are
+// long gone - every run that finishes, won or lost, gets a go at getting on it.
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Deserialize;
+use serde::Serialize;
+use crate::game::Game;
+use crate::world::World;
+
+pub const HIGH_SCORES_FILE: &str = "highscores.json";
+pub const MAX_HIGH_SCORES: usize = 10;
+
+// what each level reached and getting out alive are worth on top of the player's xp
+const DEPTH_SCORE: i32 = 100;
+const VICTORY_SCORE: i32 = 5000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: i32,
+    // the deepest level reached, not necessarily where the run ended
+    pub depth: i32,
+    pub level: i32,
+    // None for the ones who made it out
+    pub killed_by: Option<String>,
+    // YYYY-MM-DD
+    pub date: String,
+}
+
+pub fn final_score(game: &Game, world: &World) -> i32 {
+    let xp = world.fighters.get(game.player).map_or(0, |fighter| fighter.xp);
+    let victory = if game.won { VICTORY_SCORE } else { 0 };
+    xp + game.stats.deepest * DEPTH_SCORE + victory
+}
+
+pub fn high_score_entry(name: &str, game: &Game, world: &World) -> HighScore {
+    HighScore {
+        name: name.to_string(),
+        score: final_score(game, world),
+        depth: game.stats.deepest,
+        level: world.fighters.get(game.player).map_or(0, |fighter| fighter.level),
+        killed_by: if game.won { None } else { Some(game.stats.killed_by.clone().unwrap_or_else(|| "something".to_string())) },
+        date: today(),
+    }
+}
+
+// No file yet just means nobody's finished a run
+pub fn load_high_scores() -> Result<Vec<HighScore>, Box<dyn Error>> {
+    match fs::read_to_string(HIGH_SCORES_FILE) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save_high_scores(scores: &[HighScore]) -> Result<(), Box<dyn Error>> {
+    fs::write(HIGH_SCORES_FILE, serde_json::to_string_pretty(scores)?)?;
+    Ok(())
+}
+
+pub fn makes_the_table(scores: &[HighScore], score: i32) -> bool {
+    scores.len() < MAX_HIGH_SCORES || scores.iter().any(|entry| score > entry.score)
+}
+
+// Put the new score in its place, returning where that is if it stayed on the table. Ties go to whoever got there first.
+pub fn add_high_score(scores: &mut Vec<HighScore>, entry: HighScore) -> Option<usize> {
+    let position = scores.iter().position(|other| entry.score > other.score).unwrap_or(scores.len());
+    scores.insert(position, entry);
+    scores.truncate(MAX_HIGH_SCORES);
+    (position < scores.len()).then_some(position)
+}
+
+// Today's date from the system clock, worked out by hand rather than pulling in a whole date crate for it
+fn today() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Days since 1970-01-01 to a (year, month, day) date. This is Howard Hinnant's `civil_from_days`, which counts in
+// 400 year eras starting from March so leap days fall at the end of each year.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: i32) -> HighScore {
+        HighScore { name: name.to_string(), score, depth: 1, level: 1, killed_by: None, date: "2024-03-01".to_string() }
+    }
+
+    fn names(scores: &[HighScore]) -> Vec<&str> {
+        scores.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn known_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(19783), (2024, 3, 1));
+        // 2100 isn't a leap year
+        assert_eq!(civil_from_days(47540), (2100, 2, 28));
+    }
+
+    #[test]
+    fn scores_go_in_order() {
+        let mut scores = vec![entry("a", 300), entry("b", 100)];
+        assert_eq!(add_high_score(&mut scores, entry("c", 200)), Some(1));
+        assert_eq!(add_high_score(&mut scores, entry("d", 400)), Some(0));
+        assert_eq!(add_high_score(&mut scores, entry("e", 50)), Some(4));
+        assert_eq!(names(&scores), vec!["d", "a", "c", "b", "e"]);
+    }
+
+    #[test]
+    fn ties_go_to_whoever_got_there_first() {
+        let mut scores = vec![entry("a", 200), entry("b", 100)];
+        assert_eq!(add_high_score(&mut scores, entry("c", 200)), Some(1));
+        assert_eq!(names(&scores), vec!["a", "c", "b"]);
+    }
+
+    #[test]
+    fn the_table_only_keeps_the_best() {
+        let mut scores: Vec<_> = (0..MAX_HIGH_SCORES as i32).map(|i| entry(&i.to_string(), 1000 - i * 100)).collect();
+        assert!(!makes_the_table(&scores, 100));
+        assert!(makes_the_table(&scores, 101));
+
+        // not good enough, so it falls straight off the bottom
+        assert_eq!(add_high_score(&mut scores, entry("low", 100)), None);
+        assert_eq!(scores.len(), MAX_HIGH_SCORES);
+        assert!(scores.iter().all(|entry| entry.name != "low"));
+
+        // and a good one pushes the last place off
+        assert_eq!(add_high_score(&mut scores, entry("high", 950)), Some(1));
+        assert_eq!(scores.len(), MAX_HIGH_SCORES);
+        assert_eq!(scores.last().map(|entry| entry.name.as_str()), Some("8"));
+    }
+
+    #[test]
+    fn an_empty_table_takes_anything() {
+        let mut scores = vec![];
+        assert!(makes_the_table(&scores, 0));
+        assert_eq!(add_high_score(&mut scores, entry("a", 0)), Some(0));
+    }
+}
@@ -0,0 +1,465 @@
+// Level layouts. Each generator just carves floor out of solid rock and says where the rooms ended up - filling them
+// with monsters, items and the stairs is left to the game. Which generator a level gets is picked from the weights
+// in the dungeon data file.
+use std::cmp;
+use std::collections::VecDeque;
+use rand::Rng;
+use serde::Deserialize;
+use crate::data::VaultTemplate;
+use crate::dijkstra::DijkstraMap;
+use crate::game::{GameRng, Map, Tile, MAP_HEIGHT, MAP_WIDTH};
+
+const ROOM_MAX_SIZE: i32 = 10;
+const ROOM_MIN_SIZE: i32 = 6;
+const MAX_ROOMS: i32 = 30;
+
+// BSP areas stop being split once they're no bigger than this either way, and are never cut smaller than the min.
+// The min has to leave space for the smallest room.
+const BSP_MAX_LEAF_SIZE: i32 = 20;
+const BSP_MIN_LEAF_SIZE: i32 = 8;
+
+// Caves start out as noise with this chance of each tile being rock, then get smoothed this many times
+const CAVE_ROCK_CHANCE: f64 = 0.45;
+const CAVE_SMOOTHING_PASSES: i32 = 5;
+// caves have no rooms to put things in so the map gets cut up into squares this big instead
+const CAVE_AREA_SIZE: i32 = 16;
+
+// how many random spots a vault gets tried in before giving up on it
+const VAULT_PLACEMENT_TRIES: i32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Generator {
+    // rooms dropped at random wherever they fit, each joined to the one before
+    Rooms,
+    // the map split up into areas with a room in each, neighbouring areas joined together
+    Bsp,
+    // one big winding cave
+    Caves,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32
+}
+
+impl Rect {
+    pub fn new(x1: i32, y1: i32, w: i32, h: i32) -> Self {
+        Rect {x1, y1, x2: x1 + w, y2: y1 + h }
+    }
+
+    pub fn centre(&self) -> (i32, i32) {
+        let centre_x = (self.x1 + self.x2) / 2;
+        let centre_y = (self.y1 + self.y2) / 2;
+        (centre_x, centre_y)
+    }
+
+    pub fn intersects_with(&self, other: &Rect) -> bool {
+        (self.x1 <= other.x2) && (self.x2 >= other.x1) && (self.y1 <= other.y2) && (self.y2 >= other.y1)
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x1 && x < self.x2 && y >= self.y1 && y < self.y2
+    }
+}
+
+// What a generator hands back. `rooms` are where monsters and items get put - for caves they're just squares of the
+// map with some floor in them.
+pub struct Layout {
+    pub map: Map,
+    pub rooms: Vec<Rect>,
+    pub start: (i32, i32),
+    pub stairs: (i32, i32),
+}
+
+impl Layout {
+    // the player starts in the first room and the stairs go in the last one. No rooms, no level
+    fn from_rooms(map: Map, rooms: Vec<Rect>) -> Option<Self> {
+        let start = rooms.first()?.centre();
+        let stairs = rooms.last()?.centre();
+        Some(Layout { map, rooms, start, stairs })
+    }
+}
+
+// None if the generator couldn't make anything usable at all this time
+pub fn generate(generator: Generator, rng: &mut GameRng) -> Option<Layout> {
+    match generator {
+        Generator::Rooms => random_rooms(rng),
+        Generator::Bsp => bsp(rng),
+        Generator::Caves => caves(rng),
+    }
+}
+
+fn random_rooms(rng: &mut GameRng) -> Option<Layout> {
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut rooms: Vec<Rect> = vec![];
+
+    for _ in 0..MAX_ROOMS {
+        // random room size
+        let w = rng.gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
+        let h = rng.gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
+
+        // random room placememnt withing our map bounds
+        let x = rng.gen_range(0..MAP_WIDTH - w);
+        let y = rng.gen_range(0..MAP_HEIGHT- h);
+
+        let new_room = Rect::new(x, y, w, h);
+        let failed = rooms.iter().any(|r| new_room.intersects_with(r));
+        if !failed {
+            // no intersections so slap the room down
+            create_room(new_room, &mut map);
+
+            // Connect this room to the last one
+            if let Some(prev_room) = rooms.last() {
+                connect(prev_room.centre(), new_room.centre(), rng, &mut map);
+            }
+
+            rooms.push(new_room);
+        }
+    }
+
+    Layout::from_rooms(map, rooms)
+}
+
+fn bsp(rng: &mut GameRng) -> Option<Layout> {
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut rooms = vec![];
+    // leave the last row and column as wall so every room has an edge
+    bsp_split(Rect::new(0, 0, MAP_WIDTH - 1, MAP_HEIGHT - 1), rng, &mut map, &mut rooms);
+    Layout::from_rooms(map, rooms)
+}
+
+// Either cut the area in two and join up whatever ends up on each side, or put a room in it. Returns a point in one
+// of the rooms underneath so the level above can join it to its sibling.
+fn bsp_split(area: Rect, rng: &mut GameRng, map: &mut Map, rooms: &mut Vec<Rect>) -> (i32, i32) {
+    let (w, h) = (area.x2 - area.x1, area.y2 - area.y1);
+    let can_cut_x = w >= BSP_MIN_LEAF_SIZE * 2;
+    let can_cut_y = h >= BSP_MIN_LEAF_SIZE * 2;
+    let small_enough = w <= BSP_MAX_LEAF_SIZE && h <= BSP_MAX_LEAF_SIZE;
+
+    if small_enough || !(can_cut_x || can_cut_y) {
+        // a room anywhere in the area, as big as the area allows
+        let room_w = rng.gen_range(ROOM_MIN_SIZE..w + 1);
+        let room_h = rng.gen_range(ROOM_MIN_SIZE..h + 1);
+        let room = Rect::new(rng.gen_range(area.x1..area.x2 - room_w + 1), rng.gen_range(area.y1..area.y2 - room_h + 1), room_w, room_h);
+        create_room(room, map);
+        rooms.push(room);
+        return room.centre();
+    }
+
+    // cut across the longer side so the areas don't end up long and thin
+    let cut_x = match (can_cut_x, can_cut_y) {
+        (true, false) => true,
+        (false, true) => false,
+        _ if w > h + h / 4 => true,
+        _ if h > w + w / 4 => false,
+        _ => rng.gen(),
+    };
+
+    let (first, second) = if cut_x {
+        let cut = rng.gen_range(BSP_MIN_LEAF_SIZE..w - BSP_MIN_LEAF_SIZE + 1);
+        (Rect::new(area.x1, area.y1, cut, h), Rect::new(area.x1 + cut, area.y1, w - cut, h))
+    } else {
+        let cut = rng.gen_range(BSP_MIN_LEAF_SIZE..h - BSP_MIN_LEAF_SIZE + 1);
+        (Rect::new(area.x1, area.y1, w, cut), Rect::new(area.x1, area.y1 + cut, w, h - cut))
+    };
+
+    let first_point = bsp_split(first, rng, map, rooms);
+    let second_point = bsp_split(second, rng, map, rooms);
+    connect(first_point, second_point, rng, map);
+
+    if rng.gen() { first_point } else { second_point }
+}
+
+fn caves(rng: &mut GameRng) -> Option<Layout> {
+    // random noise, with the edge of the map always solid
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    for x in 1..MAP_WIDTH - 1 {
+        for y in 1..MAP_HEIGHT - 1 {
+            if !rng.gen_bool(CAVE_ROCK_CHANCE) {
+                map[x as usize][y as usize] = Tile::empty();
+            }
+        }
+    }
+
+    // each pass, anything mostly surrounded by rock becomes rock and anything mostly open becomes open
+    for _ in 0..CAVE_SMOOTHING_PASSES {
+        let mut smoothed = map.clone();
+        for x in 1..MAP_WIDTH - 1 {
+            for y in 1..MAP_HEIGHT - 1 {
+                let rock = (x - 1..x + 2)
+                    .flat_map(|nx| (y - 1..y + 2).map(move |ny| (nx, ny)))
+                    .filter(|&(nx, ny)| map[nx as usize][ny as usize].blocked)
+                    .count();
+                smoothed[x as usize][y as usize] = if rock >= 5 { Tile::wall() } else { Tile::empty() };
+            }
+        }
+        map = smoothed;
+    }
+
+    // only the biggest cave is kept - everything else gets filled in so there's nowhere that can't be reached
+    let mut regions = regions(&map);
+    regions.sort_by_key(|region| cmp::Reverse(region.len()));
+    for region in regions.iter().skip(1) {
+        for &(x, y) in region {
+            map[x as usize][y as usize] = Tile::wall();
+        }
+    }
+
+    let cave = regions.first()?;
+    let start = cave[rng.gen_range(0..cave.len())];
+    // the stairs go as far from the start as the cave allows
+    let distances = DijkstraMap::new(&map, &[start], |_, _| Some(1));
+    let stairs = cave.iter().copied().max_by_key(|&(x, y)| distances.value(x, y)).unwrap_or(start);
+
+    let mut rooms = vec![];
+    for x in (0..MAP_WIDTH).step_by(CAVE_AREA_SIZE as usize) {
+        for y in (0..MAP_HEIGHT).step_by(CAVE_AREA_SIZE as usize) {
+            // a room's edge is wall so the area goes one tile further out than the floor it covers, but the last
+            // row and column of areas get cut short by the edge of the map
+            let w = cmp::min(CAVE_AREA_SIZE + 1, MAP_WIDTH - x);
+            let h = cmp::min(CAVE_AREA_SIZE + 1, MAP_HEIGHT - y);
+            let area = Rect::new(x - 1, y - 1, w, h);
+            if cave.iter().any(|&(cx, cy)| cx >= x && cx < x + CAVE_AREA_SIZE && cy >= y && cy < y + CAVE_AREA_SIZE) {
+                rooms.push(area);
+            }
+        }
+    }
+
+    Some(Layout { map, rooms, start, stairs })
+}
+
+// Every separate patch of open floor, found by flood filling from each bit of floor not already in one
+fn regions(map: &Map) -> Vec<Vec<(i32, i32)>> {
+    let mut seen = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut regions = vec![];
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if map[x as usize][y as usize].blocked || seen[x as usize][y as usize] {
+                continue;
+            }
+
+            let mut region = vec![];
+            let mut to_visit = vec![(x, y)];
+            seen[x as usize][y as usize] = true;
+            while let Some((cx, cy)) = to_visit.pop() {
+                region.push((cx, cy));
+                for (nx, ny) in [(cx - 1, cy), (cx + 1, cy), (cx, cy - 1), (cx, cy + 1)] {
+                    let in_bounds = (0..MAP_WIDTH).contains(&nx) && (0..MAP_HEIGHT).contains(&ny);
+                    if in_bounds && !map[nx as usize][ny as usize].blocked && !seen[nx as usize][ny as usize] {
+                        seen[nx as usize][ny as usize] = true;
+                        to_visit.push((nx, ny));
+                    }
+                }
+            }
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+// Stamp a vault into solid rock somewhere it fits, then dig a tunnel from each of its ways in to the nearest open
+// floor. Tunnels never go through another vault in `vaults`, which the new one gets added to. Returns where the
+// vault's top left corner went, or None if there was nowhere for it.
+pub fn place_vault(vault: &VaultTemplate, map: &mut Map, vaults: &mut Vec<Rect>, rng: &mut GameRng) -> Option<(i32, i32)> {
+    let (w, h) = (vault.width(), vault.height());
+    // there has to be a tile of rock all round the vault that isn't the edge of the map, so tunnels can start there
+    if w > MAP_WIDTH - 4 || h > MAP_HEIGHT - 4 {
+        return None;
+    }
+
+    for _ in 0..VAULT_PLACEMENT_TRIES {
+        let x = rng.gen_range(2..MAP_WIDTH - w - 1);
+        let y = rng.gen_range(2..MAP_HEIGHT - h - 1);
+
+        // it only fits where it (and a tile around it) is solid rock, so it never cuts into anything else. Another
+        // vault's walls count as rock so those have to be checked for separately
+        let footprint = Rect::new(x, y, w, h);
+        let all_rock = (x - 1..x + w + 1).all(|cx| (y - 1..y + h + 1).all(|cy| map[cx as usize][cy as usize].blocked));
+        let with_margin = Rect::new(x - 1, y - 1, w + 1, h + 1);
+        if !all_rock || vaults.iter().any(|other| other.intersects_with(&with_margin)) {
+            continue;
+        }
+
+        for (vx, vy, chr) in vault.cells() {
+            if chr == '#' {
+                map[(x + vx) as usize][(y + vy) as usize] = Tile::wall();
+            } else if vault.is_floor(chr) {
+                map[(x + vx) as usize][(y + vy) as usize] = Tile::empty();
+            }
+        }
+        vaults.push(footprint);
+
+        for (vx, vy, chr) in vault.cells() {
+            if vault.on_edge(vx, vy) && vault.is_floor(chr) {
+                // the tile just outside the way in
+                let (dx, dy) = if vx == 0 { (-1, 0) } else if vx == w - 1 { (1, 0) } else if vy == 0 { (0, -1) } else { (0, 1) };
+                dig_to_floor((x + vx + dx, y + vy + dy), map, vaults);
+            }
+        }
+        return Some((x, y));
+    }
+    None
+}
+
+// Dig the shortest tunnel from `start` to any open floor that isn't inside a vault
+fn dig_to_floor(start: (i32, i32), map: &mut Map, vaults: &[Rect]) {
+    let index = |(x, y): (i32, i32)| (x * MAP_HEIGHT + y) as usize;
+    let mut came_from = vec![None; (MAP_WIDTH * MAP_HEIGHT) as usize];
+    let mut to_visit = VecDeque::from([start]);
+    came_from[index(start)] = Some(start);
+
+    while let Some((x, y)) = to_visit.pop_front() {
+        if !map[x as usize][y as usize].blocked {
+            // found some - carve the way back
+            let mut pos = (x, y);
+            while pos != start {
+                pos = came_from[index(pos)].unwrap();
+                map[pos.0 as usize][pos.1 as usize] = Tile::empty();
+            }
+            return;
+        }
+
+        for next in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            // never dig out the edge of the map or into a vault
+            let inside = next.0 > 0 && next.1 > 0 && next.0 < MAP_WIDTH - 1 && next.1 < MAP_HEIGHT - 1;
+            if inside && came_from[index(next)].is_none() && !vaults.iter().any(|vault| vault.contains(next.0, next.1)) {
+                came_from[index(next)] = Some((x, y));
+                to_visit.push_back(next);
+            }
+        }
+    }
+}
+
+// Everything a finished level has to get right: the edge of the map is solid so nothing has been dug off the side of
+// it, and the player's start, the stairs and everything else on the map are on open floor that can be walked to from
+// the start. Returns what's wrong, if anything.
+pub fn check_level(map: &Map, start: (i32, i32), stairs: (i32, i32), spawns: &[(String, (i32, i32))]) -> Vec<String> {
+    let mut problems = vec![];
+    if map.len() != MAP_WIDTH as usize || map.iter().any(|column| column.len() != MAP_HEIGHT as usize) {
+        problems.push(format!("the map isn't {}x{}", MAP_WIDTH, MAP_HEIGHT));
+        return problems;
+    }
+
+    let on_edge = |x: i32, y: i32| x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1;
+    if let Some((x, y)) = (0..MAP_WIDTH).flat_map(|x| (0..MAP_HEIGHT).map(move |y| (x, y)))
+        .find(|&(x, y)| on_edge(x, y) && !map[x as usize][y as usize].blocked) {
+        problems.push(format!("the edge of the map has been dug out at {},{}", x, y));
+    }
+
+    let in_bounds = |(x, y): (i32, i32)| (0..MAP_WIDTH).contains(&x) && (0..MAP_HEIGHT).contains(&y);
+    let distances = DijkstraMap::new(map, &[start], |_, _| Some(1));
+    let places = [("the start".to_string(), start), ("the way down".to_string(), stairs)];
+    for (name, (x, y)) in places.iter().chain(spawns) {
+        if !in_bounds((*x, *y)) {
+            problems.push(format!("{} is off the map at {},{}", name, x, y));
+        } else if map[*x as usize][*y as usize].blocked {
+            problems.push(format!("{} is in a wall at {},{}", name, x, y));
+        } else if distances.value(*x, *y).is_none() {
+            problems.push(format!("{} can't be reached from the start at {},{}", name, x, y));
+        }
+    }
+
+    problems
+}
+
+// An L shaped corridor between two points, going whichever way round the coin says
+fn connect((x1, y1): (i32, i32), (x2, y2): (i32, i32), rng: &mut GameRng, map: &mut Map) {
+    if rng.gen() {
+        // Horizontal tunnel then vertical
+        create_h_tunnel(x1, x2, y1, map);
+        create_v_tunnel(y1, y2, x2, map);
+    } else {
+        // Vertical then horizontal
+        create_v_tunnel(y1, y2, x1, map);
+        create_h_tunnel(x1, x2, y2, map);
+    }
+}
+
+fn create_room(room: Rect, map: &mut Map) {
+    for x in (room.x1 + 1)..room.x2 {
+        for y in (room.y1 + 1)..room.y2 {
+            map[x as usize][y as usize] = Tile::empty();
+        }
+    }
+}
+
+fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
+    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
+        map[x as usize][y as usize] = Tile::empty();
+    }
+}
+
+fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
+    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
+        map[x as usize][y as usize] = Tile::empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    // A full sized map of solid rock with the given rooms dug out of it
+    fn map_with_rooms(rooms: &[Rect]) -> Map {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        for &room in rooms {
+            create_room(room, &mut map);
+        }
+        map
+    }
+
+    #[test]
+    fn a_good_level_has_no_problems() {
+        let map = map_with_rooms(&[Rect::new(1, 1, 10, 10)]);
+        let spawns = vec![("orc".to_string(), (5, 5))];
+        assert_eq!(check_level(&map, (2, 2), (9, 9), &spawns), Vec::<String>::new());
+    }
+
+    #[test]
+    fn the_map_has_to_be_the_right_size() {
+        let map = vec![vec![Tile::empty(); 5]; 5];
+        let problems = check_level(&map, (2, 2), (3, 3), &[]);
+        assert_eq!(problems, vec![format!("the map isn't {}x{}", MAP_WIDTH, MAP_HEIGHT)]);
+    }
+
+    #[test]
+    fn the_edge_has_to_stay_solid() {
+        let mut map = map_with_rooms(&[Rect::new(1, 1, 10, 10)]);
+        map[0][4] = Tile::empty();
+        assert_eq!(check_level(&map, (2, 2), (9, 9), &[]), vec!["the edge of the map has been dug out at 0,4"]);
+    }
+
+    #[test]
+    fn everything_has_to_be_somewhere_the_player_can_get_to() {
+        let map = map_with_rooms(&[Rect::new(1, 1, 10, 10), Rect::new(20, 1, 10, 10)]);
+        let spawns = vec![
+            ("orc".to_string(), (0, 5)),
+            ("troll".to_string(), (25, 5)),
+            ("potion".to_string(), (-1, 5)),
+        ];
+        assert_eq!(check_level(&map, (2, 2), (12, 5), &spawns), vec![
+            "the way down is in a wall at 12,5",
+            "orc is in a wall at 0,5",
+            "troll can't be reached from the start at 25,5",
+            "potion is off the map at -1,5",
+        ]);
+    }
+
+    #[test]
+    fn generated_levels_pass_the_checks() {
+        for generator in [Generator::Rooms, Generator::Bsp, Generator::Caves] {
+            for seed in 0..5 {
+                let mut rng = GameRng::seed_from_u64(seed);
+                // a generator is allowed to give up now and again, just not to hand back something broken
+                if let Some(layout) = generate(generator, &mut rng) {
+                    let problems = check_level(&layout.map, layout.start, layout.stairs, &[]);
+                    assert!(problems.is_empty(), "{:?} seed {}: {:?}", generator, seed, problems);
+                }
+            }
+        }
+    }
+}
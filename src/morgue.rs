@@ -0,0 +1,120 @@
+// Morgue files: a plain text write up of how a run ended, saved when the player dies or wins. Everything in one is
+// readable without the game, so they can be passed round and compared.
+use std::cmp;
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::frontend::character_info;
+use crate::game::*;
+use crate::render::visible_renderables;
+use crate::replay::save_replay;
+use crate::world::World;
+
+pub const MORGUE_DIR: &str = "morgue";
+// how much of the message log makes it into the file
+const MORGUE_MESSAGES: usize = 20;
+
+// Where the files for a finished run go, minus the extension. The name has the time in it so runs from the same seed
+// don't overwrite each other.
+pub fn morgue_stem(game: &Game) -> Result<String, Box<dyn Error>> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    fs::create_dir_all(MORGUE_DIR)?;
+    Ok(format!("{}/{}-{}", MORGUE_DIR, game.seed, secs))
+}
+
+// Write the morgue file for a finished run, returning where it went
+pub fn write_morgue(game: &Game, world: &World, stem: &str) -> Result<String, Box<dyn Error>> {
+    let path = format!("{}.txt", stem);
+    fs::write(&path, morgue(game, world))?;
+    Ok(path)
+}
+
+// The run's replay goes alongside, since replay.json gets written over by the next game
+pub fn write_morgue_replay(game: &Game, stem: &str) -> Result<String, Box<dyn Error>> {
+    let path = format!("{}.replay.json", stem);
+    save_replay(game, &path)?;
+    Ok(path)
+}
+
+pub fn morgue(game: &Game, world: &World) -> String {
+    let mut lines = vec![];
+
+    let ending = if game.won {
+        format!("Escaped from the dungeon with the {}", game.data.dungeon.artefact)
+    } else if world.is_alive(game.player) {
+        format!("Still alive on dungeon level {}", game.depth)
+    } else {
+        format!(
+            "Killed by {} on dungeon level {}",
+            game.stats.killed_by.as_deref().unwrap_or("something"), game.depth
+        )
+    };
+    lines.push(ending);
+    lines.push(format!("Deepest level: {}, turns taken: {}", game.stats.deepest, game.stats.turns));
+
+    if let Some(info) = character_info(game, world) {
+        let hp = world.fighters.get(game.player).map_or(0, |fighter| cmp::max(fighter.hp, 0));
+        lines.push(format!("\n== Character ==\nHP: {}/{}\n{}", hp, world.max_hp(game.player), info));
+    }
+
+    lines.push("\n== Equipment ==".to_string());
+    let mut equipped: Vec<_> = world.equipped(game.player).collect();
+    equipped.sort_by_key(|&(_, eq)| eq.slot.to_string());
+    if equipped.is_empty() {
+        lines.push("Nothing".to_string());
+    }
+    for (id, eq) in equipped {
+        lines.push(format!("{}: {}", eq.slot, world.name(id)));
+    }
+
+    lines.push("\n== Inventory ==".to_string());
+    if game.inventory.is_empty() {
+        lines.push("Nothing".to_string());
+    }
+    for (idx, &id) in game.inventory.iter().enumerate() {
+        let letter = (b'a' + idx as u8) as char;
+        lines.push(format!("({}) {}", letter, world.name(id)));
+    }
+
+    lines.push(format!("\n== Kills ({}) ==", game.stats.total_kills()));
+    if game.stats.kills.is_empty() {
+        lines.push("Nothing".to_string());
+    }
+    for (name, count) in &game.stats.kills {
+        lines.push(format!("{:>4} {}", count, name));
+    }
+
+    lines.push("\n== Last messages ==".to_string());
+    let messages: Vec<_> = game.messages.iter().rev().take(MORGUE_MESSAGES).collect();
+    for (msg, _) in messages.into_iter().rev() {
+        lines.push(msg.clone());
+    }
+
+    lines.push(format!("\n== Dungeon level {} ==", game.depth));
+    lines.push(map_dump(game, world));
+    lines.join("\n") + "\n"
+}
+
+// The level as the player last saw it. Walls are `#`, floor is `.`, anywhere never seen is left blank and things are
+// drawn the same as on screen.
+fn map_dump(game: &Game, world: &World) -> String {
+    let mut rows: Vec<Vec<char>> = (0..MAP_HEIGHT).map(|y| (0..MAP_WIDTH).map(|x| {
+        let tile = game.map[x as usize][y as usize];
+        if !tile.explored {
+            ' '
+        } else if tile.block_sight {
+            '#'
+        } else {
+            '.'
+        }
+    }).collect()).collect();
+
+    for (pos, renderable) in visible_renderables(game, world) {
+        rows[pos.y as usize][pos.x as usize] = renderable.chr;
+    }
+
+    rows.into_iter().map(|row| {
+        let line: String = row.into_iter().collect();
+        line.trim_end().to_string()
+    }).collect::<Vec<_>>().join("\n")
+}
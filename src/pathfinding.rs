@@ -0,0 +1,171 @@
+// A* over the tile map. Moves go in all eight directions and a diagonal step costs the same as a straight one, the
+// same as moving does in game. Walls can't be crossed at all; anything else the caller wants avoided (e.g. monsters
+// standing in a corridor) can be given an extra cost so paths go round it when there's a sensible way round.
+use std::cmp::{self, Reverse};
+use std::collections::BinaryHeap;
+use crate::game::Map;
+
+pub(crate) const NEIGHBOURS: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+// The size of a map, for keeping something per tile in one flat Vec. It goes a column at a time, the same as the map.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Grid {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Grid {
+    pub fn of(map: &Map) -> Self {
+        Grid { width: map.len() as i32, height: map.first().map_or(0, |column| column.len()) as i32 }
+    }
+
+    pub fn tiles(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    pub fn index(&self, x: i32, y: i32) -> usize {
+        (x * self.height + y) as usize
+    }
+}
+
+// The cheapest route from `start` to `goal`, not including `start` itself. The goal tile never gets an extra cost
+// since whatever's standing there is usually the thing being chased.
+pub fn find_path(map: &Map, start: (i32, i32), goal: (i32, i32), extra_cost: impl Fn(i32, i32) -> i32) -> Option<Vec<(i32, i32)>> {
+    let grid = Grid::of(map);
+    let in_bounds = |(x, y): (i32, i32)| grid.in_bounds(x, y);
+    if !in_bounds(start) || !in_bounds(goal) {
+        return None;
+    }
+
+    let index = |(x, y): (i32, i32)| grid.index(x, y);
+    let mut best_cost = vec![i32::MAX; grid.tiles()];
+    let mut came_from = vec![None; grid.tiles()];
+    // BinaryHeap pops the biggest first, so wrap it up to get the lowest estimated total
+    let mut open = BinaryHeap::new();
+
+    best_cost[index(start)] = 0;
+    open.push(Reverse((distance(start, goal), 0, start)));
+
+    while let Some(Reverse((_, cost, pos))) = open.pop() {
+        if pos == goal {
+            return Some(rebuild_path(&came_from, index, start, goal));
+        }
+        // already found a cheaper way here since this was queued
+        if cost > best_cost[index(pos)] {
+            continue;
+        }
+
+        for (dx, dy) in NEIGHBOURS {
+            let next = (pos.0 + dx, pos.1 + dy);
+            if !in_bounds(next) || map[next.0 as usize][next.1 as usize].blocked {
+                continue;
+            }
+
+            let step_cost = if next == goal { 1 } else { 1 + extra_cost(next.0, next.1) };
+            let next_cost = cost + step_cost;
+            if next_cost < best_cost[index(next)] {
+                best_cost[index(next)] = next_cost;
+                came_from[index(next)] = Some(pos);
+                open.push(Reverse((next_cost + distance(next, goal), next_cost, next)));
+            }
+        }
+    }
+
+    None
+}
+
+// Moves needed to get between two tiles on an empty map - never more than the real cost, which A* needs
+fn distance(from: (i32, i32), to: (i32, i32)) -> i32 {
+    cmp::max((from.0 - to.0).abs(), (from.1 - to.1).abs())
+}
+
+fn rebuild_path(came_from: &[Option<(i32, i32)>], index: impl Fn((i32, i32)) -> usize, start: (i32, i32), goal: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut path = vec![goal];
+    let mut pos = goal;
+    while let Some(previous) = came_from[index(pos)] {
+        if previous == start {
+            break;
+        }
+        path.push(previous);
+        pos = previous;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::map_from;
+
+    // each step of the path is a single move onto floor
+    fn assert_walkable(map: &Map, start: (i32, i32), path: &[(i32, i32)]) {
+        let mut pos = start;
+        for &next in path {
+            assert!(distance(pos, next) == 1, "{:?} to {:?} isn't one step", pos, next);
+            assert!(!map[next.0 as usize][next.1 as usize].blocked, "{:?} is a wall", next);
+            pos = next;
+        }
+    }
+
+    #[test]
+    fn straight_across_open_ground() {
+        let map = map_from(&[".....", ".....", "....."]);
+        let path = find_path(&map, (0, 0), (4, 2), |_, _| 0).unwrap();
+        // diagonals cost the same, so it's as many steps as the longer side
+        assert_eq!(path.len(), 4);
+        assert_eq!(path.last(), Some(&(4, 2)));
+        assert!(!path.contains(&(0, 0)));
+        assert_walkable(&map, (0, 0), &path);
+    }
+
+    #[test]
+    fn goes_round_walls() {
+        let map = map_from(&[
+            ".....",
+            "####.",
+            ".....",
+        ]);
+        let path = find_path(&map, (0, 0), (0, 2), |_, _| 0).unwrap();
+        assert_eq!(path.len(), 8);
+        assert_eq!(path.last(), Some(&(0, 2)));
+        assert_walkable(&map, (0, 0), &path);
+    }
+
+    #[test]
+    fn no_path_when_walled_off() {
+        let map = map_from(&["..#.."]);
+        assert_eq!(find_path(&map, (0, 0), (4, 0), |_, _| 0), None);
+        // or when either end is off the map
+        assert_eq!(find_path(&map, (0, 0), (5, 0), |_, _| 0), None);
+        assert_eq!(find_path(&map, (-1, 0), (1, 0), |_, _| 0), None);
+    }
+
+    #[test]
+    fn extra_cost_sends_paths_round() {
+        let map = map_from(&[".....", ".....", "....."]);
+        // something standing in the middle of the room
+        let path = find_path(&map, (0, 1), (4, 1), |x, y| if (x, y) == (2, 1) { 10 } else { 0 }).unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(!path.contains(&(2, 1)));
+        assert_walkable(&map, (0, 1), &path);
+    }
+
+    #[test]
+    fn extra_cost_only_goes_round_when_there_is_a_way_round() {
+        let map = map_from(&["....."]);
+        let path = find_path(&map, (0, 0), (4, 0), |x, _| if x == 2 { 10 } else { 0 }).unwrap();
+        assert_eq!(path, vec![(1, 0), (2, 0), (3, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn the_goal_never_costs_extra() {
+        let map = map_from(&["...", "...", "..."]);
+        let path = find_path(&map, (0, 1), (1, 1), |x, y| if (x, y) == (1, 1) { 100 } else { 0 }).unwrap();
+        assert_eq!(path, vec![(1, 1)]);
+    }
+}
@@ -0,0 +1,436 @@
+// Drawing the game. Everything here goes through the `Renderer` trait so the same layout code can put the game
+// in a libtcod window or into a plain grid of characters (handy for checking the layout without a display)
+use serde::Serialize;
+use crate::colors::*;
+use crate::fov::Fov;
+use crate::game::*;
+use crate::world::*;
+
+pub const SCREEN_WIDTH: i32 = 80;
+pub const SCREEN_HEIGHT: i32 = 50;
+pub const PANEL_HEIGHT: i32 = 7;
+const PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_HEIGHT;
+const BAR_WIDTH: i32 = 20;
+const MSG_X: i32 = BAR_WIDTH + 2;
+const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
+const MSG_HEIGHT: usize = PANEL_HEIGHT as usize - 1;
+
+// colour defs
+const DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
+const LIGHT_WALL: Color = Color { r: 130, g: 110, b: 50};
+const DARK_GROUND: Color = Color { r: 50, g: 50, b: 150 };
+const LIGHT_GROUND: Color = Color { r: 200, g: 180, b: 50 };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+}
+
+// The handful of drawing operations the game actually needs. Coordinates are screen cells.
+pub trait Renderer {
+    fn clear_screen(&mut self);
+    fn draw_char(&mut self, x: i32, y: i32, chr: char, colour: Color);
+    fn draw_background(&mut self, x: i32, y: i32, colour: Color);
+    fn draw_text(&mut self, x: i32, y: i32, alignment: TextAlignment, colour: Color, text: &str);
+    // print word-wrapped text and return how many lines it took up
+    fn draw_text_rect(&mut self, x: i32, y: i32, width: i32, colour: Color, text: &str) -> i32;
+    fn text_height(&self, width: i32, text: &str) -> i32;
+    fn present(&mut self);
+
+    fn fill_background(&mut self, x: i32, y: i32, width: i32, height: i32, colour: Color) {
+        for cy in y..y + height {
+            for cx in x..x + width {
+                self.draw_background(cx, cy, colour);
+            }
+        }
+    }
+}
+
+// A single character cell in the in-memory backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    chr: char,
+    foreground: Color,
+    background: Color,
+}
+
+// What the in-memory backend has drawn: one string per screen row plus the colours for every cell
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Frame {
+    pub lines: Vec<String>,
+    pub foreground: Vec<Vec<Color>>,
+    pub background: Vec<Vec<Color>>,
+}
+
+// In-memory backend. Draws into a grid of characters so a frame can be compared as plain text
+pub struct TextRenderer {
+    width: i32,
+    height: i32,
+    cells: Vec<Cell>,
+}
+
+impl TextRenderer {
+    pub fn new(width: i32, height: i32) -> Self {
+        TextRenderer { width, height, cells: vec![TextRenderer::blank(); (width * height) as usize] }
+    }
+
+    fn blank() -> Cell {
+        Cell { chr: ' ', foreground: WHITE, background: BLACK }
+    }
+
+    fn cell_mut(&mut self, x: i32, y: i32) -> Option<&mut Cell> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        self.cells.get_mut((y * self.width + x) as usize)
+    }
+
+    pub fn frame(&self) -> Frame {
+        let rows = self.cells.chunks(self.width as usize);
+        Frame {
+            lines: rows.clone().map(|row| row.iter().map(|cell| cell.chr).collect()).collect(),
+            foreground: rows.clone().map(|row| row.iter().map(|cell| cell.foreground).collect()).collect(),
+            background: rows.map(|row| row.iter().map(|cell| cell.background).collect()).collect(),
+        }
+    }
+}
+
+impl Renderer for TextRenderer {
+    fn clear_screen(&mut self) {
+        self.cells.iter_mut().for_each(|cell| *cell = TextRenderer::blank());
+    }
+
+    fn draw_char(&mut self, x: i32, y: i32, chr: char, colour: Color) {
+        if let Some(cell) = self.cell_mut(x, y) {
+            cell.chr = chr;
+            cell.foreground = colour;
+        }
+    }
+
+    fn draw_background(&mut self, x: i32, y: i32, colour: Color) {
+        if let Some(cell) = self.cell_mut(x, y) {
+            cell.background = colour;
+        }
+    }
+
+    fn draw_text(&mut self, x: i32, y: i32, alignment: TextAlignment, colour: Color, text: &str) {
+        let len = text.chars().count() as i32;
+        let start = match alignment {
+            TextAlignment::Left => x,
+            TextAlignment::Center => x - len / 2,
+        };
+        for (i, chr) in text.chars().enumerate() {
+            self.draw_char(start + i as i32, y, chr, colour);
+        }
+    }
+
+    fn draw_text_rect(&mut self, x: i32, y: i32, width: i32, colour: Color, text: &str) -> i32 {
+        let lines = wrap_text(text, width);
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(x, y + i as i32, TextAlignment::Left, colour, line);
+        }
+        lines.len() as i32
+    }
+
+    fn text_height(&self, width: i32, text: &str) -> i32 {
+        wrap_text(text, width).len() as i32
+    }
+
+    fn present(&mut self) {}
+}
+
+// Word wrap some text to the given width, honouring any line breaks already in it
+pub fn wrap_text(text: &str, width: i32) -> Vec<String> {
+    let width = width.max(1) as usize;
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let line_len = line.chars().count();
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + 1 + word_len > width {
+                lines.push(line);
+                line = String::new();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+
+            // words longer than the whole line just get chopped up
+            while line.chars().count() > width {
+                let rest = line.chars().skip(width).collect();
+                lines.push(line.chars().take(width).collect());
+                line = rest;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn get_names_under_mouse(mouse: (i32, i32), world: &World, fov_map: &Fov) -> String {
+    let (x, y) = mouse;
+    if !(0..MAP_WIDTH).contains(&x) || !(0..MAP_HEIGHT).contains(&y) || !fov_map.is_in_fov(x, y) {
+        return String::new();
+    }
+    // anything without a renderable (e.g. a hidden trap) can't be seen so it doesn't get named either
+    let names = world.at(x, y).filter(|&id| world.renderables.contains(id)).map(|id| world.name(id)).collect::<Vec<_>>();
+    names.join(", ")
+}
+
+// render functions
+pub fn render_all(r: &mut dyn Renderer, game: &Game, world: &World, mouse: (i32, i32)) {
+    r.clear_screen();
+
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            let visible = game.fov.is_in_fov(x, y);
+            let wall = game.map[x as usize][y as usize].block_sight;
+            let colour = match (visible, wall) {
+                // outside FOV
+                (false, false) => DARK_GROUND,
+                (false, true) => DARK_WALL,
+                // inside FOV
+                (true, false) => LIGHT_GROUND,
+                (true, true) => LIGHT_WALL
+            };
+
+            // the game core keeps track of what's been explored - we just draw it
+            if game.map[x as usize][y as usize].explored {
+                r.draw_background(x, y, colour);
+            }
+        }
+    }
+
+    for (pos, renderable) in visible_renderables(game, world) {
+        draw_object(pos, renderable, r);
+    }
+
+    // Render stats panel
+    r.fill_background(0, PANEL_Y, SCREEN_WIDTH, PANEL_HEIGHT, BLACK);
+
+    // render in game messages
+    // Go backwards from latest to earlier. some message lines may wrap so we won't always know we have th ecorrect number to render
+    let mut y = MSG_HEIGHT as i32;
+    for &(ref msg, colour) in game.messages.iter().rev() {
+        let msg_height = r.text_height(MSG_WIDTH, msg);
+        y -= msg_height;
+        if y < 0 {
+            break;
+        }
+
+        r.draw_text_rect(MSG_X, PANEL_Y + y, MSG_WIDTH, colour, msg);
+    }
+
+    r.draw_text(1, PANEL_Y, TextAlignment::Left, LIGHT_GREY, &get_names_under_mouse(mouse, world, &game.fov));
+
+    // get the relevant player stats
+    let hp = world.fighters.get(game.player).map_or(0, |f| f.hp);
+    let max_hp = world.max_hp(game.player);
+    render_bar(r, 1, PANEL_Y + 1, BAR_WIDTH, "HP", hp, max_hp, LIGHT_RED, DARKER_RED);
+
+    r.draw_text(1, PANEL_Y + 2, TextAlignment::Left, WHITE, &format!("Dungeon Level: {}", game.depth));
+
+    // and anything currently affecting them, with the turns left on each
+    if let Some(effects) = world.effects.get(game.player) {
+        let effects = effects.iter()
+            .map(|status| format!("{} ({})", status.effect.describe(), status.turns))
+            .collect::<Vec<_>>();
+        r.draw_text_rect(1, PANEL_Y + 3, BAR_WIDTH, LIGHT_YELLOW, &effects.join(", "));
+    }
+}
+
+// Everything the player can see on the map, in the order to draw it - whatever's in view, plus anything always visible
+// (like the stairs) once it's been explored. Only things with both somewhere to be and something to look like count.
+pub fn visible_renderables<'a>(game: &Game, world: &'a World) -> Vec<(Position, &'a Renderable)> {
+    let mut to_draw: Vec<_> = world.renderables.iter()
+        .filter_map(|(id, renderable)| world.pos(id).map(|pos| (id, pos, renderable)))
+        .filter(|&(_, pos, renderable)|
+            game.fov.is_in_fov(pos.x, pos.y) ||
+            (renderable.always_visible && game.map[pos.x as usize][pos.y as usize].explored)
+        ).collect();
+
+    // blocking things (monsters, the player) go on top of whatever they're standing on
+    to_draw.sort_by_key(|&(id, _, _)| world.blocks.contains(id));
+    to_draw.into_iter().map(|(_, pos, renderable)| (pos, renderable)).collect()
+}
+
+// draw the Object (this includes setting the colour appropriately etc)
+// Note - the `dyn` keyword dentoes that we're working on a trait rather than a concrete type
+fn draw_object(pos: Position, renderable: &Renderable, r: &mut dyn Renderer) {
+    r.draw_char(pos.x, pos.y, renderable.chr, renderable.colour);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_bar(r: &mut dyn Renderer, x: i32, y: i32, total_width: i32, name: &str, value: i32, maximum: i32, bar_colour: Color, back_colour: Color) {
+    // render a bar to track a stat (e.g. hp, xp, etc)
+    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
+
+    // render background
+    r.fill_background(x, y, total_width, 1, back_colour);
+
+    // render the actual bar
+    if bar_width > 0 {
+        r.fill_background(x, y, bar_width, 1, bar_colour);
+    }
+
+    // Let's print the actual value as well
+    r.draw_text(x + total_width / 2, y, TextAlignment::Center, WHITE, &format!("{}: {}/{}", name, value, maximum));
+}
+
+// Draw a menu window in the middle of the screen. Waiting for the answer is up to the front end.
+pub fn draw_menu<T: AsRef<str>>(r: &mut dyn Renderer, header: &str, options: &[T], width: i32) {
+    assert!(options.len() <= 26, "Cannot have more than 26 options");
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        r.text_height(width, header)
+    };
+    let height = options.len() as i32 + header_height;
+
+    let x = SCREEN_WIDTH/2 - width/2;
+    let y = SCREEN_HEIGHT/2 - height/2;
+    r.fill_background(x, y, width, height, BLACK);
+    if !header.is_empty() {
+        r.draw_text_rect(x, y, width, WHITE, header);
+    }
+
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text.as_ref());
+        r.draw_text(x, y + header_height + index as i32, TextAlignment::Left, WHITE, &text);
+    }
+}
+
+// Title text for the main menu. Any background picture is left to the front end.
+pub fn draw_title(r: &mut dyn Renderer) {
+    r.draw_text(SCREEN_WIDTH/2, SCREEN_HEIGHT/2 - 5, TextAlignment::Center, YELLOW, "WITTY GAME TITLE");
+    r.draw_text(SCREEN_WIDTH/2, SCREEN_HEIGHT/2 - 3, TextAlignment::Center, YELLOW, "By Learning Rust");
+}
+
+// Show how far through a replay we are, in the spare space under the dungeon level
+pub fn draw_replay_status(r: &mut dyn Renderer, step: usize, total: usize, state: &str) {
+    r.draw_text(1, PANEL_Y + 5, TextAlignment::Left, LIGHT_CYAN, &format!("Replay: {}/{}", step, total));
+    r.draw_text(1, PANEL_Y + 6, TextAlignment::Left, LIGHT_CYAN, state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use rand::SeedableRng;
+    use crate::data::GameData;
+
+    // A small room in the top corner of an otherwise solid map, with the player half dead in the middle of it and an
+    // orc for company
+    fn test_game() -> (Game, World) {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        for column in &mut map[1..11] {
+            for tile in &mut column[1..9] {
+                *tile = Tile::empty();
+            }
+        }
+
+        let mut world = World::new();
+        let player = world.spawn_at("Player", 5, 5, '@', WHITE, true);
+        world.fighters.insert(player, Fighter { max_hp: 30, hp: 15, defence: 2, power: 5, xp: 0, level: 1, on_death: DeathCallback::Player });
+        world.spawn_at("orc", 7, 5, 'o', GREEN, true);
+
+        let mut game = Game {
+            map, messages: Messages::new(), inventory: vec![], player, depth: 3, levels: Default::default(),
+            stats: Default::default(), won: false, seed: 0, rng: GameRng::seed_from_u64(0), commands: vec![],
+            data: Rc::new(GameData::default()), fov: Fov::new(MAP_WIDTH, MAP_HEIGHT), disturbed: false,
+            sight: Fov::new(MAP_WIDTH, MAP_HEIGHT),
+        };
+        update_fov(&mut game, &world);
+        (game, world)
+    }
+
+    fn render(game: &Game, world: &World) -> Frame {
+        let mut screen = TextRenderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+        render_all(&mut screen, game, world, (-1, -1));
+        screen.frame()
+    }
+
+    #[test]
+    fn the_map_and_everything_on_it_gets_drawn() {
+        let (game, world) = test_game();
+        let frame = render(&game, &world);
+        assert_eq!(frame.lines.len(), SCREEN_HEIGHT as usize);
+        assert_eq!(&frame.lines[5][4..8], " @ o");
+        assert_eq!(frame.foreground[5][5], WHITE);
+        assert_eq!(frame.foreground[5][7], GREEN);
+        assert_eq!(frame.background[5][5], LIGHT_GROUND);
+        assert_eq!(frame.background[0][5], LIGHT_WALL);
+        // nowhere near the room hasn't been seen, so it's left black
+        assert_eq!(frame.background[30][60], BLACK);
+    }
+
+    #[test]
+    fn the_hp_bar_shows_how_hurt_the_player_is() {
+        let (game, world) = test_game();
+        let frame = render(&game, &world);
+        let y = (PANEL_Y + 1) as usize;
+        assert_eq!(&frame.lines[y][1..21], "      HP: 15/30     ");
+        // half the bar is full and half is empty
+        assert!(frame.background[y][1..11].iter().all(|&colour| colour == LIGHT_RED));
+        assert!(frame.background[y][11..21].iter().all(|&colour| colour == DARKER_RED));
+        assert_eq!(frame.background[y][21], BLACK);
+        assert!(frame.lines[y + 1].starts_with(" Dungeon Level: 3"));
+    }
+
+    #[test]
+    fn the_latest_messages_go_at_the_bottom_of_the_panel() {
+        let (mut game, world) = test_game();
+        game.messages.add("First", WHITE);
+        game.messages.add(format!("Second {}", "x".repeat(MSG_WIDTH as usize)), RED);
+        game.messages.add("Third", LIGHT_BLUE);
+        let frame = render(&game, &world);
+
+        let line = |y: i32| frame.lines[(PANEL_Y + y) as usize][MSG_X as usize..].trim_end().to_string();
+        assert_eq!(line(5), "Third");
+        assert_eq!(frame.foreground[(PANEL_Y + 5) as usize][MSG_X as usize], LIGHT_BLUE);
+        // the long one wraps onto two lines
+        assert_eq!(line(3), "Second");
+        assert_eq!(line(4), "x".repeat(MSG_WIDTH as usize));
+        assert_eq!(frame.foreground[(PANEL_Y + 4) as usize][MSG_X as usize], RED);
+        assert_eq!(line(2), "First");
+    }
+
+    #[test]
+    fn old_messages_drop_off_the_top() {
+        let (mut game, world) = test_game();
+        for i in 0..10 {
+            game.messages.add(format!("Message {}", i), WHITE);
+        }
+        let frame = render(&game, &world);
+        let panel = frame.lines[PANEL_Y as usize..].join("\n");
+        assert!(panel.contains("Message 9"));
+        assert!(panel.contains("Message 4"));
+        assert!(!panel.contains("Message 3"));
+    }
+
+    #[test]
+    fn wrap_text_breaks_between_words() {
+        assert_eq!(wrap_text("the quick brown fox", 10), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap_text("short", 10), vec!["short"]);
+        assert_eq!(wrap_text("", 10), vec![""]);
+    }
+
+    #[test]
+    fn wrap_text_chops_up_words_longer_than_the_line() {
+        assert_eq!(wrap_text("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap_text("a abcdefgh b", 4), vec!["a", "abcd", "efgh", "b"]);
+        // a width of nothing still makes progress
+        assert_eq!(wrap_text("abc", 0), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn wrap_text_keeps_line_breaks() {
+        assert_eq!(wrap_text("one\ntwo three", 20), vec!["one", "two three"]);
+        assert_eq!(wrap_text("one\n\ntwo", 20), vec!["one", "", "two"]);
+        assert_eq!(wrap_text("one two\nthree", 5), vec!["one", "two", "three"]);
+    }
+}
@@ -0,0 +1,45 @@
+// Recording and replaying whole runs. A run is just its seed plus every command the player gave - all the randomness
+// comes from the seeded RNG, so feeding the same commands into a new game with the same seed plays out the same game.
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::rc::Rc;
+use serde::Deserialize;
+use serde::Serialize;
+use crate::data::GameData;
+use crate::game::*;
+use crate::world::World;
+
+pub const REPLAY_FILE: &str = "replay.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub commands: Vec<PlayerCommand>,
+}
+
+pub fn save_replay(game: &Game, path: &str) -> Result<(), Box<dyn Error>> {
+    let replay = Replay { seed: game.seed, commands: game.commands.clone() };
+    let replay_data = serde_json::to_string(&replay)?;
+    let mut file = File::create(path)?;
+    file.write_all(replay_data.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_replay(path: &str) -> Result<Replay, Box<dyn Error>> {
+    let mut replay_data = String::new();
+    let mut file = File::open(path)?;
+    file.read_to_string(&mut replay_data)?;
+    let replay = serde_json::from_str::<Replay>(&replay_data)?;
+    Ok(replay)
+}
+
+// Run the whole replay straight through with no front end. Good for checking a crash report still crashes.
+pub fn run_replay(replay: &Replay, data: Rc<GameData>) -> Result<(Game, World), Box<dyn Error>> {
+    let (mut game, mut world) = new_game(replay.seed, data)?;
+    for &command in &replay.commands {
+        play_turn(command, &mut game, &mut world);
+    }
+    Ok((game, world))
+}
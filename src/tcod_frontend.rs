@@ -0,0 +1,156 @@
+// The libtcod window front end. Only built with the `tcod` feature - everything else gets by without libtcod, so
+// colours and text alignment get converted to libtcod's own types on the way in.
+use tcod::colors;
+use tcod::console::{self, BackgroundFlag, Console, FontLayout, FontType, Root};
+use tcod::image::{self, Image};
+use tcod::input::{self, Event, KeyCode, Mouse};
+use crate::colors::*;
+use crate::frontend::*;
+use crate::render::*;
+
+const LIMIT_FPS: i32 = 20;
+
+pub struct Tcod {
+    root: Root,
+    mouse: Mouse,
+    menu_background: Image,
+}
+
+impl Tcod {
+    pub fn new() -> Self {
+        // Initialise and create the root window
+        let root = Root::initializer()
+            .font("arial10x10.png", FontLayout::Tcod)
+            .font_type(FontType::Greyscale)
+            .size(SCREEN_WIDTH, SCREEN_HEIGHT)
+            .title("Making a window happen")
+            .init();
+
+        // limit FPS (doesn't really matter for a key input roguelike)
+        tcod::system::set_fps(LIMIT_FPS);
+
+        Tcod {
+            root,
+            mouse: Default::default(),
+            menu_background: Image::from_file("menu_background.png").expect("Background image not found"),
+        }
+    }
+}
+
+// Turn a libtcod key into one of ours. Printable keys turn up as `Text` when polling and as `Char` when waiting.
+fn convert_key(key: input::Key) -> Option<Key> {
+    use KeyCode::*;
+    match key.code {
+        Up | NumPad8 => Some(Key::Up),
+        Down | NumPad2 => Some(Key::Down),
+        Left | NumPad4 => Some(Key::Left),
+        Right | NumPad6 => Some(Key::Right),
+        Home | NumPad7 => Some(Key::Home),
+        End | NumPad1 => Some(Key::End),
+        PageUp | NumPad9 => Some(Key::PageUp),
+        PageDown | NumPad3 => Some(Key::PageDown),
+        NumPad5 => Some(Key::Centre),
+        Enter | NumPadEnter => Some(Key::Enter),
+        Escape => Some(Key::Escape),
+        Backspace => Some(Key::Backspace),
+        Text => key.text().chars().next().map(Key::Char),
+        Char if key.printable != '\0' => Some(Key::Char(key.printable)),
+        _ => None,
+    }
+}
+
+fn convert_colour(colour: Color) -> colors::Color {
+    colors::Color { r: colour.r, g: colour.g, b: colour.b }
+}
+
+fn convert_alignment(alignment: TextAlignment) -> console::TextAlignment {
+    match alignment {
+        TextAlignment::Left => console::TextAlignment::Left,
+        TextAlignment::Center => console::TextAlignment::Center,
+    }
+}
+
+// just draw straight onto the root console
+impl Renderer for Root {
+    fn clear_screen(&mut self) {
+        self.set_default_background(convert_colour(BLACK));
+        self.clear();
+    }
+
+    fn draw_char(&mut self, x: i32, y: i32, chr: char, colour: Color) {
+        self.set_default_foreground(convert_colour(colour));
+        self.put_char(x, y, chr, BackgroundFlag::None);
+    }
+
+    fn draw_background(&mut self, x: i32, y: i32, colour: Color) {
+        self.set_char_background(x, y, convert_colour(colour), BackgroundFlag::Set);
+    }
+
+    fn draw_text(&mut self, x: i32, y: i32, alignment: TextAlignment, colour: Color, text: &str) {
+        self.set_default_foreground(convert_colour(colour));
+        self.print_ex(x, y, BackgroundFlag::None, convert_alignment(alignment), text);
+    }
+
+    fn draw_text_rect(&mut self, x: i32, y: i32, width: i32, colour: Color, text: &str) -> i32 {
+        self.set_default_foreground(convert_colour(colour));
+        self.print_rect(x, y, width, 0, text);
+        self.get_height_rect(x, y, width, 0, text)
+    }
+
+    fn text_height(&self, width: i32, text: &str) -> i32 {
+        self.get_height_rect(0, 0, width, 0, text)
+    }
+
+    fn present(&mut self) {
+        self.flush();
+    }
+}
+
+impl Frontend for Tcod {
+    fn renderer(&mut self) -> &mut dyn Renderer {
+        &mut self.root
+    }
+
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        // This call panics. :(
+        // Might be time to move away from tcod
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, Event::Mouse(m))) => {
+                self.mouse = m;
+                Some(InputEvent::Mouse { x: m.cx as i32, y: m.cy as i32, left_click: m.lbutton_pressed, right_click: m.rbutton_pressed })
+            },
+            Some((_, Event::Key(k))) if k.code == KeyCode::Enter && k.alt => {
+                let fullscreen = self.root.is_fullscreen();
+                self.root.set_fullscreen(!fullscreen);
+                None
+            },
+            // the same key also turns up as `Text` so don't count it twice
+            Some((_, Event::Key(k))) if k.code == KeyCode::Char => None,
+            Some((_, Event::Key(k))) => convert_key(k).map(InputEvent::Key),
+            None => None,
+        }
+    }
+
+    fn wait_for_key(&mut self) -> Key {
+        loop {
+            if self.root.window_closed() {
+                return Key::Escape;
+            }
+            if let Some(key) = convert_key(self.root.wait_for_keypress(true)) {
+                return key;
+            }
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.root.window_closed()
+    }
+
+    fn mouse_pos(&self) -> (i32, i32) {
+        (self.mouse.cx as i32, self.mouse.cy as i32)
+    }
+
+    fn draw_menu_background(&mut self) {
+        image::blit_2x(&self.menu_background, (0, 0), (-1, -1), &mut self.root, (0, 0));
+    }
+}
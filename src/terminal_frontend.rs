@@ -0,0 +1,204 @@
+// A front end for plain terminals. The screen is drawn with ANSI escape codes (24 bit colour) and the keyboard is
+// read in raw mode, so it plays fine over ssh or in WSL without an X server.
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::{execute, terminal};
+use crate::colors::Color;
+use crate::frontend::*;
+use crate::render::*;
+
+// how long to wait for input before drawing another frame
+const FRAME_TIME: Duration = Duration::from_millis(50);
+
+pub struct Terminal {
+    screen: TextRenderer,
+    // what's actually on the terminal right now, so only the rows that change get redrawn
+    drawn: Option<Frame>,
+    out: Stdout,
+    mouse: (i32, i32),
+    closed: bool,
+}
+
+impl Terminal {
+    pub fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        // switch to the alternate screen and hide the cursor
+        write!(out, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        execute!(out, EnableMouseCapture)?;
+
+        Ok(Terminal {
+            screen: TextRenderer::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            drawn: None,
+            out,
+            mouse: (-1, -1),
+            closed: false,
+        })
+    }
+
+    fn convert_key(&mut self, key: KeyEvent) -> Option<Key> {
+        // windows sends releases as well as presses
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.closed = true;
+                Some(Key::Escape)
+            },
+            KeyCode::Up => Some(Key::Up),
+            KeyCode::Down => Some(Key::Down),
+            KeyCode::Left => Some(Key::Left),
+            KeyCode::Right => Some(Key::Right),
+            KeyCode::Home => Some(Key::Home),
+            KeyCode::End => Some(Key::End),
+            KeyCode::PageUp => Some(Key::PageUp),
+            KeyCode::PageDown => Some(Key::PageDown),
+            KeyCode::KeypadBegin | KeyCode::Char('.') => Some(Key::Centre),
+            KeyCode::Enter => Some(Key::Enter),
+            KeyCode::Esc => Some(Key::Escape),
+            KeyCode::Backspace => Some(Key::Backspace),
+            KeyCode::Char(chr) => Some(Key::Char(chr)),
+            _ => None,
+        }
+    }
+
+    fn convert_event(&mut self, event: Event) -> Option<InputEvent> {
+        match event {
+            Event::Key(key) => self.convert_key(key).map(InputEvent::Key),
+            Event::Mouse(mouse) => {
+                let (x, y) = (mouse.column as i32, mouse.row as i32);
+                self.mouse = (x, y);
+                match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => Some(InputEvent::Mouse { x, y, left_click: true, right_click: false }),
+                    MouseEventKind::Down(MouseButton::Right) => Some(InputEvent::Mouse { x, y, left_click: false, right_click: true }),
+                    MouseEventKind::Moved | MouseEventKind::Drag(_) => Some(InputEvent::Mouse { x, y, left_click: false, right_click: false }),
+                    _ => None,
+                }
+            },
+            Event::Resize(_, _) => {
+                // the terminal has probably been scribbled over so draw everything again next time
+                self.drawn = None;
+                None
+            },
+            _ => None,
+        }
+    }
+
+    fn read_event(&mut self) -> Option<Event> {
+        match event::read() {
+            Ok(event) => Some(event),
+            Err(_) => {
+                self.closed = true;
+                None
+            }
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // put the terminal back how we found it - nothing much we can do if this fails
+        let _ = execute!(self.out, DisableMouseCapture);
+        let _ = write!(self.out, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = self.out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn colour_code(foreground: Color, background: Color) -> String {
+    format!("\x1b[38;2;{};{};{};48;2;{};{};{}m", foreground.r, foreground.g, foreground.b, background.r, background.g, background.b)
+}
+
+impl Renderer for Terminal {
+    fn clear_screen(&mut self) {
+        self.screen.clear_screen();
+    }
+
+    fn draw_char(&mut self, x: i32, y: i32, chr: char, colour: Color) {
+        self.screen.draw_char(x, y, chr, colour);
+    }
+
+    fn draw_background(&mut self, x: i32, y: i32, colour: Color) {
+        self.screen.draw_background(x, y, colour);
+    }
+
+    fn draw_text(&mut self, x: i32, y: i32, alignment: TextAlignment, colour: Color, text: &str) {
+        self.screen.draw_text(x, y, alignment, colour, text);
+    }
+
+    fn draw_text_rect(&mut self, x: i32, y: i32, width: i32, colour: Color, text: &str) -> i32 {
+        self.screen.draw_text_rect(x, y, width, colour, text)
+    }
+
+    fn text_height(&self, width: i32, text: &str) -> i32 {
+        self.screen.text_height(width, text)
+    }
+
+    fn present(&mut self) {
+        let frame = self.screen.frame();
+        let mut output = String::new();
+
+        for (y, line) in frame.lines.iter().enumerate() {
+            let unchanged = self.drawn.as_ref().is_some_and(|drawn|
+                drawn.lines[y] == *line && drawn.foreground[y] == frame.foreground[y] && drawn.background[y] == frame.background[y]
+            );
+            if unchanged {
+                continue;
+            }
+
+            // move to the start of the row (ANSI rows and columns count from 1)
+            output.push_str(&format!("\x1b[{};1H", y + 1));
+            let mut colours = None;
+            for (x, chr) in line.chars().enumerate() {
+                let cell_colours = (frame.foreground[y][x], frame.background[y][x]);
+                if colours != Some(cell_colours) {
+                    output.push_str(&colour_code(cell_colours.0, cell_colours.1));
+                    colours = Some(cell_colours);
+                }
+                output.push(chr);
+            }
+        }
+
+        output.push_str("\x1b[0m");
+        self.out.write_all(output.as_bytes()).expect("Couldn't draw to the terminal");
+        self.out.flush().expect("Couldn't draw to the terminal");
+        self.drawn = Some(frame);
+    }
+}
+
+impl Frontend for Terminal {
+    fn renderer(&mut self) -> &mut dyn Renderer {
+        self
+    }
+
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        match event::poll(FRAME_TIME) {
+            Ok(true) => self.read_event().and_then(|event| self.convert_event(event)),
+            Ok(false) => None,
+            Err(_) => {
+                self.closed = true;
+                None
+            }
+        }
+    }
+
+    fn wait_for_key(&mut self) -> Key {
+        while !self.closed {
+            if let Some(InputEvent::Key(key)) = self.read_event().and_then(|event| self.convert_event(event)) {
+                return key;
+            }
+        }
+        Key::Escape
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn mouse_pos(&self) -> (i32, i32) {
+        self.mouse
+    }
+}
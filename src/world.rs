@@ -0,0 +1,178 @@
+// Everything in the dungeon is an entity - just a handle - and what it is comes from the components it's been given.
+// A potion is a name, a renderable and an item; an orc adds a fighter, an AI and something that blocks the way.
+// Carried items have no position, so anything that only cares about the map never sees them.
+use serde::Deserialize;
+use serde::Serialize;
+use crate::colors::Color;
+use crate::entity::*;
+use crate::game::{Actor, Effect, Equipment, Fighter, Inflicts, Item, StatusEffect, AI};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32) -> Self {
+        Position { x, y }
+    }
+
+    pub fn distance_to(&self, other: Position) -> f32 {
+        self.distance(other.x, other.y)
+    }
+
+    pub fn distance(&self, x: i32, y: i32) -> f32 {
+        let dx = x - self.x;
+        let dy = y - self.y;
+        ((dx*dx + dy*dy) as f32).sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Renderable {
+    pub chr: char,
+    pub colour: Color,
+    // still drawn once explored, even out of sight (e.g. the stairs)
+    pub always_visible: bool,
+}
+
+// marks things that nothing else can walk through
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Blocks;
+
+// Something nasty hidden in the floor. It has nothing to draw until it's been set off, then it gets this
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Trap {
+    pub revealed: Renderable,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct World {
+    entities: Entities<()>,
+    pub names: Components<String>,
+    pub positions: Components<Position>,
+    pub renderables: Components<Renderable>,
+    pub blocks: Components<Blocks>,
+    pub fighters: Components<Fighter>,
+    pub ais: Components<AI>,
+    pub actors: Components<Actor>,
+    pub effects: Components<Vec<StatusEffect>>,
+    // what a monster's attacks or a trap do to whoever they hit
+    pub inflicts: Components<Inflicts>,
+    pub traps: Components<Trap>,
+    pub items: Components<Item>,
+    pub equipment: Components<Equipment>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // A new entity with nothing but a name
+    pub fn spawn(&mut self, name: &str) -> EntityId {
+        let id = self.entities.insert(());
+        self.names.insert(id, name.into());
+        id
+    }
+
+    // The usual set of components for something sitting on the map
+    pub fn spawn_at(&mut self, name: &str, x: i32, y: i32, chr: char, colour: Color, blocks: bool) -> EntityId {
+        let id = self.spawn(name);
+        self.positions.insert(id, Position::new(x, y));
+        self.renderables.insert(id, Renderable { chr, colour, always_visible: false });
+        if blocks {
+            self.blocks.insert(id, Blocks);
+        }
+        id
+    }
+
+    pub fn despawn(&mut self, id: EntityId) {
+        self.names.remove(id);
+        self.positions.remove(id);
+        self.renderables.remove(id);
+        self.blocks.remove(id);
+        self.fighters.remove(id);
+        self.ais.remove(id);
+        self.actors.remove(id);
+        self.effects.remove(id);
+        self.inflicts.remove(id);
+        self.traps.remove(id);
+        self.items.remove(id);
+        self.equipment.remove(id);
+        self.entities.remove(id);
+    }
+
+    // A copy of every entity's handle
+    pub fn ids(&self) -> Vec<EntityId> {
+        self.entities.ids()
+    }
+
+    // Get rid of every entity `keep` says no to
+    pub fn retain(&mut self, mut keep: impl FnMut(EntityId) -> bool) {
+        for id in self.entities.ids() {
+            if !keep(id) {
+                self.despawn(id);
+            }
+        }
+    }
+
+    pub fn name(&self, id: EntityId) -> &str {
+        self.names.get(id).map_or("something", |name| name.as_str())
+    }
+
+    pub fn pos(&self, id: EntityId) -> Option<Position> {
+        self.positions.get(id).copied()
+    }
+
+    // Things with a fighter are alive until their hit points run out
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.fighters.get(id).is_some_and(|fighter| fighter.hp > 0)
+    }
+
+    // Whether this is something that can be picked up and put in an inventory
+    pub fn is_carryable(&self, id: EntityId) -> bool {
+        self.items.contains(id) || self.equipment.contains(id)
+    }
+
+    // Everything this entity has equipped
+    pub fn equipped(&self, owner: EntityId) -> impl Iterator<Item = (EntityId, &Equipment)> + '_ {
+        self.equipment.iter().filter(move |(_, eq)| eq.equipped_by == Some(owner))
+    }
+
+    // A fighter's stats with everything it has equipped added on
+    pub fn power(&self, id: EntityId) -> i32 {
+        self.fighters.get(id).map_or(0, |f| f.power) + self.equipped(id).map(|(_, eq)| eq.power_bonus).sum::<i32>()
+    }
+
+    pub fn defence(&self, id: EntityId) -> i32 {
+        self.fighters.get(id).map_or(0, |f| f.defence) + self.equipped(id).map(|(_, eq)| eq.defence_bonus).sum::<i32>()
+    }
+
+    pub fn max_hp(&self, id: EntityId) -> i32 {
+        self.fighters.get(id).map_or(0, |f| f.max_hp) + self.equipped(id).map(|(_, eq)| eq.max_hp_bonus).sum::<i32>()
+    }
+
+    pub fn has_effect(&self, id: EntityId, effect: Effect) -> bool {
+        self.effects.get(id).is_some_and(|effects| effects.iter().any(|status| status.effect == effect))
+    }
+
+    // How much energy this gains every tick, with haste and slow taken into account. Never less than 1 so everything
+    // gets a go eventually
+    pub fn speed(&self, id: EntityId) -> i32 {
+        let mut speed = self.actors.get(id).map_or(0, |actor| actor.speed);
+        if self.has_effect(id, Effect::Hasted) {
+            speed *= 2;
+        }
+        if self.has_effect(id, Effect::Slowed) {
+            speed /= 2;
+        }
+        speed.max(1)
+    }
+
+    // Everything standing on the given tile
+    pub fn at(&self, x: i32, y: i32) -> impl Iterator<Item = EntityId> + '_ {
+        self.positions.iter().filter(move |(_, pos)| pos.x == x && pos.y == y).map(|(id, _)| id)
+    }
+}